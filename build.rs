@@ -1,4 +1,5 @@
 use image::imageops::FilterType;
+use image::{Rgba, RgbaImage};
 use std::env;
 use std::fs;
 use std::io::Cursor;
@@ -6,6 +7,11 @@ use std::path::{Path, PathBuf};
 
 const ICON_SOURCE: &str = "assets/nocaps-icon.png";
 const WINDOW_ICON_SIZE: u32 = 64;
+// Tray colours borrowed from the window palette: amber for the held layer,
+// the alarm red for a stopped keyboard backend.
+const AMBER: Rgba<u8> = Rgba([255, 196, 92, 255]);
+const ALARM: Rgba<u8> = Rgba([255, 104, 92, 255]);
+const WHITE: Rgba<u8> = Rgba([255, 255, 255, 255]);
 
 fn main() {
     println!("cargo:rerun-if-changed={ICON_SOURCE}");
@@ -20,12 +26,66 @@ fn main() {
         .into_rgba8();
     fs::write(out_dir.join("nocaps.rgba"), window_icon.as_raw())
         .expect("write generated window icon");
+    write_tray_icons(&out_dir, &window_icon);
 
     if env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("windows") {
         embed_windows_icon(&out_dir, &source);
     }
 }
 
+/// One tray icon per remapper state, all derived from the window icon so they
+/// stay recognisably the same glyph.
+fn write_tray_icons(out_dir: &Path, icon: &RgbaImage) {
+    type Paint = fn(&mut RgbaImage);
+    let variants: [(&str, Paint); 4] = [
+        ("enabled", |_| {}),
+        ("disabled", dim),
+        ("layer", glow),
+        ("error", alarm),
+    ];
+    for (state, paint) in variants {
+        let mut image = icon.clone();
+        paint(&mut image);
+        fs::write(out_dir.join(format!("tray-{state}.rgba")), image.as_raw())
+            .expect("write generated tray icon");
+    }
+}
+
+/// Grey and half transparent: present, but visibly switched off.
+fn dim(image: &mut RgbaImage) {
+    for pixel in image.pixels_mut() {
+        let [r, g, b, a] = pixel.0;
+        let grey = ((u32::from(r) * 3 + u32::from(g) * 6 + u32::from(b)) / 10) as u8;
+        *pixel = Rgba([grey, grey, grey, a / 2]);
+    }
+}
+
+/// The green keycap turns amber while Caps Lock is held as the layer key.
+fn glow(image: &mut RgbaImage) {
+    for pixel in image.pixels_mut() {
+        let [r, g, b, a] = pixel.0;
+        if g > r && g > b {
+            *pixel = Rgba([AMBER[0], AMBER[1], AMBER[2], a]);
+        }
+    }
+}
+
+/// The disabled glyph with a red "!" badge in the empty top-left corner.
+fn alarm(image: &mut RgbaImage) {
+    dim(image);
+    let unit = image.width() / 16;
+    let mut fill = |x: u32, y: u32, width: u32, height: u32, colour: Rgba<u8>| {
+        for dy in 0..height * unit {
+            for dx in 0..width * unit {
+                image.put_pixel(x * unit + dx, y * unit + dy, colour);
+            }
+        }
+    };
+    fill(0, 0, 6, 7, ALARM);
+    fill(2, 1, 2, 3, WHITE);
+    fill(2, 5, 2, 1, WHITE);
+}
+
 fn embed_windows_icon(out_dir: &Path, source: &image::DynamicImage) {
     let mut directory = ico::IconDir::new(ico::ResourceType::Icon);
     for size in [16, 24, 32, 48, 64, 128, 256] {
@@ -0,0 +1,142 @@
+use super::palette::Palette;
+use super::{NocapsApp, Status};
+use crate::diagnostics::WorkerState;
+use eframe::egui;
+
+impl NocapsApp {
+    /// Every input device the Linux backend looked at, its worker and the
+    /// virtual keyboard, as reported by `Diagnostics`.
+    pub(super) fn diagnostics_view(&mut self, ui: &mut egui::Ui) {
+        let palette = self.palette;
+        let locale = self.locale;
+        let diagnostics = self.controller.runtime().diagnostics();
+        ui.horizontal(|ui| {
+            ui.label(
+                egui::RichText::new(format!("[ {} ]", locale.text("diagnostics.heading")))
+                    .color(palette.accent)
+                    .size(12.0)
+                    .strong(),
+            );
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui
+                    .add(egui::Button::new(
+                        egui::RichText::new(locale.text("diagnostics.copy"))
+                            .color(palette.dim)
+                            .size(12.0),
+                    ))
+                    .on_hover_text(locale.text("diagnostics.copy_hover"))
+                    .clicked()
+                {
+                    ui.ctx()
+                        .copy_text(diagnostics.report(crate::platform::key_code_name));
+                    self.status = Some(Status {
+                        is_error: false,
+                        message: locale.text("status.report_copied").to_owned(),
+                    });
+                }
+            });
+        });
+        ui.add_space(6.0);
+
+        let label = |ui: &mut egui::Ui, text: &str, color: egui::Color32| {
+            ui.label(egui::RichText::new(text).color(color).size(11.0))
+        };
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                label(ui, locale.text("diagnostics.devices"), palette.dim);
+                let devices = diagnostics.devices();
+                if devices.is_empty() {
+                    label(ui, locale.text("diagnostics.no_devices"), palette.faint);
+                }
+                egui::Grid::new("nocaps-diagnostics")
+                    .num_columns(5)
+                    .spacing(egui::vec2(10.0, 2.0))
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for heading in [
+                            "diagnostics.device",
+                            "diagnostics.name",
+                            "diagnostics.id",
+                            "diagnostics.verdict",
+                            "diagnostics.worker",
+                        ] {
+                            label(ui, locale.text(heading), palette.faint);
+                        }
+                        ui.end_row();
+                        for device in &devices {
+                            label(ui, &device.path, palette.dim);
+                            label(ui, &device.name, palette.text);
+                            label(ui, &device.id, palette.dim);
+                            let (verdict, color) = if device.included {
+                                ("diagnostics.included", palette.accent)
+                            } else {
+                                ("diagnostics.skipped", palette.faint)
+                            };
+                            label(ui, locale.text(verdict), color).on_hover_text(&device.reason);
+                            match device.worker() {
+                                Some(worker) => {
+                                    let (state, color) = worker_state(worker.state(), palette);
+                                    let response = label(ui, locale.text(state), color);
+                                    if let Some(error) = worker.error() {
+                                        response.on_hover_text(error);
+                                    }
+                                }
+                                None => {
+                                    label(ui, "-", palette.faint);
+                                }
+                            }
+                            ui.end_row();
+                        }
+                    });
+                if let Some(stalled) = diagnostics.stalled() {
+                    label(
+                        ui,
+                        &locale.fill("diagnostics.stalled", &[("name", &stalled)]),
+                        palette.alarm,
+                    );
+                }
+
+                ui.add_space(10.0);
+                label(ui, locale.text("diagnostics.output"), palette.dim);
+                match diagnostics.output() {
+                    Some(output) => {
+                        label(
+                            ui,
+                            &format!("{}  {}", output.name, output.path.as_deref().unwrap_or("-")),
+                            palette.text,
+                        );
+                        label(
+                            ui,
+                            &locale.fill(
+                                "diagnostics.keys",
+                                &[("count", &output.keys.len().to_string())],
+                            ),
+                            palette.dim,
+                        );
+                        let keys: Vec<String> = output
+                            .keys
+                            .iter()
+                            .map(|&code| crate::platform::key_code_name(code))
+                            .collect();
+                        label(ui, &keys.join(" "), palette.faint);
+                    }
+                    None => {
+                        label(ui, locale.text("diagnostics.not_created"), palette.alarm);
+                    }
+                }
+            });
+    }
+}
+
+/// The message and colour a keyboard worker's state is shown in.
+fn worker_state(state: WorkerState, palette: &Palette) -> (&'static str, egui::Color32) {
+    match state {
+        WorkerState::Starting => ("diagnostics.starting", palette.dim),
+        WorkerState::Grabbed => ("diagnostics.grabbed", palette.accent),
+        WorkerState::Released => ("diagnostics.released", palette.dim),
+        WorkerState::Stopped => ("diagnostics.stopped", palette.alarm),
+        WorkerState::Abandoned => ("diagnostics.abandoned", palette.alarm),
+        WorkerState::Unplugged => ("diagnostics.unplugged", palette.dim),
+    }
+}
@@ -0,0 +1,57 @@
+use crate::locale::Locale;
+use eframe::egui;
+use std::sync::Arc;
+
+/// System fonts that cover Chinese and Japanese, tried in order. They are only
+/// loaded for languages that need them, as each is tens of megabytes.
+#[cfg(target_os = "linux")]
+const CJK_FONTS: &[&str] = &[
+    "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/google-noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/google-noto-sans-cjk-fonts/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/adobe-source-han-sans/SourceHanSans-Regular.ttc",
+    "/usr/share/fonts/truetype/wqy/wqy-microhei.ttc",
+    "/usr/share/fonts/wenquanyi/wqy-microhei/wqy-microhei.ttc",
+];
+#[cfg(target_os = "macos")]
+const CJK_FONTS: &[&str] = &[
+    "/System/Library/Fonts/Hiragino Sans GB.ttc",
+    "/System/Library/Fonts/ヒラギノ角ゴシック W3.ttc",
+    "/Library/Fonts/Arial Unicode.ttf",
+];
+#[cfg(windows)]
+const CJK_FONTS: &[&str] = &[
+    "C:\\Windows\\Fonts\\msyh.ttc",
+    "C:\\Windows\\Fonts\\YuGothM.ttc",
+    "C:\\Windows\\Fonts\\msgothic.ttc",
+];
+
+/// The bundled monospace font first, so the interface keeps reading like a
+/// terminal, then a system CJK font for glyphs it lacks when `locale` needs one.
+pub(super) fn configure_fonts(context: &egui::Context, locale: &Locale) {
+    let mut fonts = egui::FontDefinitions::empty();
+    fonts.font_data.insert(
+        "nocaps-mono".to_owned(),
+        Arc::new(egui::FontData::from_static(include_bytes!(
+            "../../assets/DejaVuSansMono.ttf"
+        ))),
+    );
+    let mut families = vec!["nocaps-mono".to_owned()];
+    if locale.needs_cjk_font() {
+        match CJK_FONTS.iter().find_map(|path| std::fs::read(path).ok()) {
+            Some(bytes) => {
+                fonts.font_data.insert(
+                    "nocaps-cjk".to_owned(),
+                    Arc::new(egui::FontData::from_owned(bytes)),
+                );
+                families.push("nocaps-cjk".to_owned());
+            }
+            None => log::warn!("no CJK font found; install Noto Sans CJK to read this language"),
+        }
+    }
+    for family in [egui::FontFamily::Proportional, egui::FontFamily::Monospace] {
+        fonts.families.insert(family, families.clone());
+    }
+    context.set_fonts(fonts);
+}
@@ -0,0 +1,409 @@
+use super::palette::Palette;
+use crate::config::{Action, InputKey};
+use crate::locale::Locale;
+use crate::usage::UsageCounts;
+use eframe::egui;
+
+/// One cap of a drawn keyboard, `width` in key widths. Keys nocaps cannot
+/// bind are drawn as labelled blanks so the rows keep their shape.
+enum Cap {
+    Key(InputKey, f32),
+    Blank(&'static str, f32),
+}
+
+/// The physical layout the keyboard diagrams are drawn in. ISO boards have the
+/// tall Enter and an extra key beside left Shift, which nocaps cannot bind.
+#[derive(Clone, Copy, PartialEq)]
+pub(super) enum KeyboardLayout {
+    Ansi,
+    Iso,
+}
+
+impl KeyboardLayout {
+    fn rows(self) -> &'static [&'static [Cap]] {
+        match self {
+            Self::Ansi => ANSI,
+            Self::Iso => ISO,
+        }
+    }
+
+    pub(super) fn name(self) -> &'static str {
+        match self {
+            Self::Ansi => "ANSI",
+            Self::Iso => "ISO",
+        }
+    }
+}
+
+const ANSI: &[&[Cap]] = &[
+    FUNCTION_ROW,
+    NUMBER_ROW,
+    ANSI_TOP_ROW,
+    ANSI_HOME_ROW,
+    ANSI_BOTTOM_ROW,
+    SPACE_ROW,
+    NAVIGATION_ROW,
+    EXTRA_FUNCTION_ROW,
+];
+
+const ISO: &[&[Cap]] = &[
+    FUNCTION_ROW,
+    NUMBER_ROW,
+    ISO_TOP_ROW,
+    ISO_HOME_ROW,
+    ISO_BOTTOM_ROW,
+    SPACE_ROW,
+    NAVIGATION_ROW,
+    EXTRA_FUNCTION_ROW,
+];
+
+const FUNCTION_ROW: &[Cap] = {
+    use Cap::{Blank, Key};
+    use InputKey::*;
+    &[
+        Key(Escape, 1.0),
+        Blank("", 1.0),
+        Key(F1, 1.0),
+        Key(F2, 1.0),
+        Key(F3, 1.0),
+        Key(F4, 1.0),
+        Key(F5, 1.0),
+        Key(F6, 1.0),
+        Key(F7, 1.0),
+        Key(F8, 1.0),
+        Key(F9, 1.0),
+        Key(F10, 1.0),
+        Key(F11, 1.0),
+        Key(F12, 1.0),
+    ]
+};
+
+const NUMBER_ROW: &[Cap] = {
+    use Cap::Key;
+    use InputKey::*;
+    &[
+        Key(Backquote, 1.0),
+        Key(Digit1, 1.0),
+        Key(Digit2, 1.0),
+        Key(Digit3, 1.0),
+        Key(Digit4, 1.0),
+        Key(Digit5, 1.0),
+        Key(Digit6, 1.0),
+        Key(Digit7, 1.0),
+        Key(Digit8, 1.0),
+        Key(Digit9, 1.0),
+        Key(Digit0, 1.0),
+        Key(Minus, 1.0),
+        Key(Equal, 1.0),
+        Key(Backspace, 2.0),
+    ]
+};
+
+const ANSI_TOP_ROW: &[Cap] = {
+    use Cap::Key;
+    use InputKey::*;
+    &[
+        Key(Tab, 1.5),
+        Key(Q, 1.0),
+        Key(W, 1.0),
+        Key(E, 1.0),
+        Key(R, 1.0),
+        Key(T, 1.0),
+        Key(Y, 1.0),
+        Key(U, 1.0),
+        Key(I, 1.0),
+        Key(O, 1.0),
+        Key(P, 1.0),
+        Key(LeftBracket, 1.0),
+        Key(RightBracket, 1.0),
+        Key(Backslash, 1.5),
+    ]
+};
+
+const ANSI_HOME_ROW: &[Cap] = {
+    use Cap::{Blank, Key};
+    use InputKey::*;
+    &[
+        Blank("caps", 1.75),
+        Key(A, 1.0),
+        Key(S, 1.0),
+        Key(D, 1.0),
+        Key(F, 1.0),
+        Key(G, 1.0),
+        Key(H, 1.0),
+        Key(J, 1.0),
+        Key(K, 1.0),
+        Key(L, 1.0),
+        Key(Semicolon, 1.0),
+        Key(Quote, 1.0),
+        Key(Enter, 2.25),
+    ]
+};
+
+const ANSI_BOTTOM_ROW: &[Cap] = {
+    use Cap::{Blank, Key};
+    use InputKey::*;
+    &[
+        Blank("shift", 2.25),
+        Key(Z, 1.0),
+        Key(X, 1.0),
+        Key(C, 1.0),
+        Key(V, 1.0),
+        Key(B, 1.0),
+        Key(N, 1.0),
+        Key(M, 1.0),
+        Key(Comma, 1.0),
+        Key(Period, 1.0),
+        Key(Slash, 1.0),
+        Blank("shift", 2.75),
+    ]
+};
+
+// The tall ISO Enter is drawn in the top row, leaving a gap below it.
+const ISO_TOP_ROW: &[Cap] = {
+    use Cap::Key;
+    use InputKey::*;
+    &[
+        Key(Tab, 1.5),
+        Key(Q, 1.0),
+        Key(W, 1.0),
+        Key(E, 1.0),
+        Key(R, 1.0),
+        Key(T, 1.0),
+        Key(Y, 1.0),
+        Key(U, 1.0),
+        Key(I, 1.0),
+        Key(O, 1.0),
+        Key(P, 1.0),
+        Key(LeftBracket, 1.0),
+        Key(RightBracket, 1.0),
+        Key(Enter, 1.5),
+    ]
+};
+
+const ISO_HOME_ROW: &[Cap] = {
+    use Cap::{Blank, Key};
+    use InputKey::*;
+    &[
+        Blank("caps", 1.75),
+        Key(A, 1.0),
+        Key(S, 1.0),
+        Key(D, 1.0),
+        Key(F, 1.0),
+        Key(G, 1.0),
+        Key(H, 1.0),
+        Key(J, 1.0),
+        Key(K, 1.0),
+        Key(L, 1.0),
+        Key(Semicolon, 1.0),
+        Key(Quote, 1.0),
+        Key(Backslash, 1.0),
+        Blank("", 1.25),
+    ]
+};
+
+const ISO_BOTTOM_ROW: &[Cap] = {
+    use Cap::{Blank, Key};
+    use InputKey::*;
+    &[
+        Blank("shift", 1.25),
+        Blank("<>", 1.0),
+        Key(Z, 1.0),
+        Key(X, 1.0),
+        Key(C, 1.0),
+        Key(V, 1.0),
+        Key(B, 1.0),
+        Key(N, 1.0),
+        Key(M, 1.0),
+        Key(Comma, 1.0),
+        Key(Period, 1.0),
+        Key(Slash, 1.0),
+        Blank("shift", 2.75),
+    ]
+};
+
+const SPACE_ROW: &[Cap] = {
+    use Cap::{Blank, Key};
+    use InputKey::*;
+    &[Blank("", 4.0), Key(Space, 6.0)]
+};
+
+const NAVIGATION_ROW: &[Cap] = {
+    use Cap::{Blank, Key};
+    use InputKey::*;
+    &[
+        Key(Insert, 1.0),
+        Key(Home, 1.0),
+        Key(PageUp, 1.0),
+        Key(Delete, 1.0),
+        Key(End, 1.0),
+        Key(PageDown, 1.0),
+        Blank("", 1.0),
+        Key(ArrowLeft, 1.0),
+        Key(ArrowUp, 1.0),
+        Key(ArrowDown, 1.0),
+        Key(ArrowRight, 1.0),
+    ]
+};
+
+const EXTRA_FUNCTION_ROW: &[Cap] = {
+    use Cap::Key;
+    use InputKey::*;
+    &[
+        Key(F13, 1.0),
+        Key(F14, 1.0),
+        Key(F15, 1.0),
+        Key(F16, 1.0),
+        Key(F17, 1.0),
+        Key(F18, 1.0),
+        Key(F19, 1.0),
+        Key(F20, 1.0),
+        Key(F21, 1.0),
+        Key(F22, 1.0),
+        Key(F23, 1.0),
+        Key(F24, 1.0),
+    ]
+};
+
+/// Lays out `layout` row by row. Blanks are drawn here; each bindable key is
+/// handed to `key` with the size it should allocate.
+pub(super) fn draw_keyboard(
+    ui: &mut egui::Ui,
+    palette: &Palette,
+    layout: KeyboardLayout,
+    height: f32,
+    mut key: impl FnMut(&mut egui::Ui, InputKey, egui::Vec2),
+) {
+    let unit = (ui.available_width() / 15.0).min(40.0);
+    for row in layout.rows() {
+        ui.horizontal(|ui| {
+            ui.spacing_mut().item_spacing = egui::Vec2::ZERO;
+            for cap in *row {
+                match *cap {
+                    Cap::Key(input, width) => key(ui, input, egui::vec2(unit * width, height)),
+                    Cap::Blank(label, width) => {
+                        let (rect, _) = ui.allocate_exact_size(
+                            egui::vec2(unit * width, height),
+                            egui::Sense::hover(),
+                        );
+                        if !label.is_empty() {
+                            let rect = rect.shrink(1.5);
+                            ui.painter().rect_stroke(
+                                rect,
+                                0.0,
+                                egui::Stroke::new(1.0, palette.faint),
+                                egui::StrokeKind::Inside,
+                            );
+                            ui.painter().text(
+                                rect.center(),
+                                egui::Align2::CENTER_CENTER,
+                                label,
+                                egui::FontId::monospace(9.0),
+                                palette.faint,
+                            );
+                        }
+                    }
+                }
+            }
+        });
+    }
+}
+
+/// Paints the keyboard with each key shaded from the panel colour towards
+/// the accent colour by how often it invoked an action. The square root keeps rarely used
+/// keys visible next to the favourites.
+pub(super) fn draw_heatmap(
+    ui: &mut egui::Ui,
+    palette: &Palette,
+    locale: &Locale,
+    layout: KeyboardLayout,
+    counts: &UsageCounts,
+) {
+    let most = counts.keys.values().copied().max().unwrap_or(0).max(1) as f32;
+    let height = (ui.available_width() / 15.0).min(40.0) * 0.8;
+    draw_keyboard(ui, palette, layout, height, |ui, key, size| {
+        let (rect, response) = ui.allocate_exact_size(size, egui::Sense::hover());
+        let rect = rect.shrink(1.5);
+        let count = counts.keys.get(&key).copied().unwrap_or(0);
+        let heat = (count as f32 / most).sqrt();
+        let fill = if count == 0 {
+            palette.panel
+        } else {
+            palette
+                .panel
+                .lerp_to_gamma(palette.accent, 0.2 + 0.8 * heat)
+        };
+        let painter = ui.painter();
+        painter.rect_filled(rect, 0.0, fill);
+        painter.rect_stroke(
+            rect,
+            0.0,
+            egui::Stroke::new(1.0, palette.faint),
+            egui::StrokeKind::Inside,
+        );
+        painter.text(
+            rect.center(),
+            egui::Align2::CENTER_CENTER,
+            key_legend(locale, key),
+            egui::FontId::monospace(10.0),
+            if heat > 0.6 {
+                palette.screen
+            } else {
+                palette.text
+            },
+        );
+        response.on_hover_text(locale.fill(
+            "usage.key_count",
+            &[("key", locale.key(key)), ("count", &count.to_string())],
+        ));
+    });
+}
+
+/// What is printed on a drawn keycap; shorter than `InputKey::label`.
+pub(super) fn key_legend(locale: &Locale, key: InputKey) -> &str {
+    locale.text(match key {
+        InputKey::Escape => "legend.escape",
+        InputKey::Backspace => "legend.backspace",
+        InputKey::Delete => "legend.delete",
+        InputKey::Insert => "legend.insert",
+        InputKey::PageUp => "legend.page_up",
+        InputKey::PageDown => "legend.page_down",
+        InputKey::ArrowUp => return "↑",
+        InputKey::ArrowDown => return "↓",
+        InputKey::ArrowLeft => return "←",
+        InputKey::ArrowRight => return "→",
+        InputKey::Enter => "legend.enter",
+        InputKey::Space => "legend.space",
+        _ => return key.label(),
+    })
+}
+
+/// An action as it fits on a drawn keycap, under the key's own legend.
+pub(super) fn action_legend(locale: &Locale, action: Action) -> &str {
+    locale.text(match action {
+        Action::LeftControl => "legend.control",
+        Action::LeftShift => "legend.shift",
+        Action::LeftAlt => "legend.alt",
+        Action::LeftMeta => "legend.meta",
+        Action::ArrowUp => return "↑",
+        Action::ArrowDown => return "↓",
+        Action::ArrowLeft => return "←",
+        Action::ArrowRight => return "→",
+        Action::Home => "legend.home",
+        Action::End => "legend.end",
+        Action::PageUp => "legend.page_up",
+        Action::PageDown => "legend.page_down",
+        Action::Backspace => "legend.backspace",
+        Action::Delete => "legend.delete",
+        Action::Enter => "legend.enter",
+        Action::Escape => "legend.escape",
+        Action::Tab => "legend.tab",
+        Action::Space => "legend.space",
+        Action::VolumeUp => "legend.volume_up",
+        Action::VolumeDown => "legend.volume_down",
+        Action::VolumeMute => "legend.mute",
+        Action::MediaPrevious => "legend.previous",
+        Action::MediaPlayPause => "legend.play",
+        Action::MediaNext => "legend.next",
+    })
+}
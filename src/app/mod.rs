@@ -0,0 +1,2160 @@
+use crate::config::{
+    Action, Category, Config, InputKey, Language, ProfileError, RuntimeEvent, Theme,
+};
+use crate::controller::{Controller, Notice};
+use crate::handoff::Handoff;
+use crate::history::History;
+#[cfg(target_os = "linux")]
+use crate::inspector::Output;
+use crate::locale::{locale, Locale, Message};
+#[cfg(target_os = "linux")]
+use crate::service::{Installation, Service};
+use anyhow::{Context, Result};
+use arc_swap::{ArcSwap, ArcSwapOption};
+use eframe::egui;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use tray_icon::menu::{CheckMenuItem, Menu, MenuEvent, MenuId, MenuItem, Submenu};
+use tray_icon::{TrayIcon, TrayIconBuilder};
+
+#[cfg(target_os = "linux")]
+mod diagnostics;
+mod fonts;
+mod keyboard;
+mod palette;
+#[cfg(target_os = "linux")]
+mod setup;
+
+use fonts::configure_fonts;
+use keyboard::{action_legend, draw_heatmap, draw_keyboard, key_legend, KeyboardLayout};
+use palette::{apply_palette, draw_scanlines, palette, Palette};
+
+const ICON_SIZE: u32 = 64;
+const ICON_RGBA: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/nocaps.rgba"));
+const TRAY_ENABLED_RGBA: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/tray-enabled.rgba"));
+const TRAY_DISABLED_RGBA: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/tray-disabled.rgba"));
+const TRAY_LAYER_RGBA: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/tray-layer.rgba"));
+const TRAY_ERROR_RGBA: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/tray-error.rgba"));
+
+pub fn run(
+    controller: Arc<Controller>,
+    running: Arc<AtomicBool>,
+    startup_error: Option<String>,
+) -> Result<()> {
+    let show_on_start = startup_error.is_some();
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default()
+            .with_title("nocaps")
+            .with_inner_size([560.0, 590.0])
+            .with_min_inner_size([540.0, 550.0])
+            .with_resizable(true)
+            .with_visible(show_on_start)
+            .with_icon(window_icon()),
+        ..Default::default()
+    };
+
+    eframe::run_native(
+        "nocaps",
+        options,
+        Box::new(move |creation_context| {
+            configure_style(&creation_context.egui_ctx);
+            let app = NocapsApp::new(
+                controller,
+                running,
+                startup_error,
+                &creation_context.egui_ctx,
+            )
+            .map_err(|error| -> Box<dyn std::error::Error + Send + Sync> { error.into() })?;
+            Ok(Box::new(app))
+        }),
+    )
+    .map_err(|error| anyhow::anyhow!(error.to_string()))
+}
+
+/// What the tray icon currently shows, most urgent first.
+#[derive(Clone, Copy, PartialEq)]
+enum TrayState {
+    Error,
+    Layer,
+    Enabled,
+    Disabled,
+}
+
+impl TrayState {
+    fn rgba(self) -> &'static [u8] {
+        match self {
+            Self::Error => TRAY_ERROR_RGBA,
+            Self::Layer => TRAY_LAYER_RGBA,
+            Self::Enabled => TRAY_ENABLED_RGBA,
+            Self::Disabled => TRAY_DISABLED_RGBA,
+        }
+    }
+
+    fn tooltip(self, locale: &Locale) -> &str {
+        locale.text(match self {
+            Self::Error => "tray.tooltip_error",
+            Self::Layer => "tray.tooltip_layer",
+            Self::Enabled => "tray.tooltip_enabled",
+            Self::Disabled => "tray.tooltip_disabled",
+        })
+    }
+
+    fn icon(self) -> Result<tray_icon::Icon> {
+        tray_icon::Icon::from_rgba(self.rgba().to_vec(), ICON_SIZE, ICON_SIZE)
+            .context("create nocaps tray icon")
+    }
+}
+
+struct Tray {
+    icon: TrayIcon,
+    state: TrayState,
+    locale: &'static Locale,
+    enabled: MenuItem,
+    profiles: Submenu,
+    // One check item per profile, rebuilt only when the set of names changes.
+    profile_items: Vec<(CheckMenuItem, String)>,
+    configure: MenuItem,
+    quit: MenuItem,
+}
+
+impl Tray {
+    fn new(config: &Config, locale: &'static Locale) -> Result<Self> {
+        let menu = Menu::new();
+        let enabled = MenuItem::new(enabled_menu_text(locale, config.enabled), true, None);
+        let profiles = Submenu::new(locale.text("tray.profiles"), true);
+        let configure = MenuItem::new(locale.text("tray.configure"), true, None);
+        let quit = MenuItem::new(locale.text("tray.quit"), true, None);
+        menu.append(&enabled).context("add Enabled tray item")?;
+        menu.append(&profiles)
+            .context("add Profiles tray submenu")?;
+        menu.append(&configure).context("add Configure tray item")?;
+        menu.append(&quit).context("add Quit tray item")?;
+
+        let state = if config.enabled {
+            TrayState::Enabled
+        } else {
+            TrayState::Disabled
+        };
+        let tray_icon = TrayIconBuilder::new()
+            .with_tooltip(state.tooltip(locale))
+            .with_icon(state.icon()?)
+            .with_menu(Box::new(menu))
+            .build()
+            .context("create system tray icon")?;
+
+        let mut tray = Self {
+            icon: tray_icon,
+            state,
+            locale,
+            enabled,
+            profiles,
+            profile_items: Vec::new(),
+            configure,
+            quit,
+        };
+        tray.show(config);
+        Ok(tray)
+    }
+
+    /// Mirrors `config` in the menu: the Enabled text and a check beside the
+    /// active profile.
+    fn show(&mut self, config: &Config) {
+        self.enabled
+            .set_text(enabled_menu_text(self.locale, config.enabled));
+        let unchanged = self
+            .profile_items
+            .iter()
+            .map(|(_, name)| name)
+            .eq(config.profiles.keys());
+        if !unchanged {
+            for (item, _) in self.profile_items.drain(..) {
+                let _ = self.profiles.remove(&item);
+            }
+            for name in config.profiles.keys() {
+                let item = CheckMenuItem::new(name, true, false, None);
+                if self.profiles.append(&item).is_ok() {
+                    self.profile_items.push((item, name.clone()));
+                }
+            }
+        }
+        for (item, name) in &self.profile_items {
+            item.set_checked(*name == config.profile);
+        }
+    }
+
+    /// Swaps the icon and tooltip, touching the native tray only on a change.
+    fn set_state(&mut self, state: TrayState) {
+        if state == self.state {
+            return;
+        }
+        match state.icon() {
+            Ok(icon) => {
+                if let Err(error) = self.icon.set_icon(Some(icon)) {
+                    log::warn!("could not update the tray icon: {error}");
+                    return;
+                }
+            }
+            Err(error) => {
+                log::warn!("{error:#}");
+                return;
+            }
+        }
+        let _ = self.icon.set_tooltip(Some(state.tooltip(self.locale)));
+        self.state = state;
+    }
+
+    /// Re-labels the menu and tooltip in another language.
+    fn relabel(&mut self, locale: &'static Locale, config: &Config) {
+        self.locale = locale;
+        self.profiles.set_text(locale.text("tray.profiles"));
+        self.configure.set_text(locale.text("tray.configure"));
+        self.quit.set_text(locale.text("tray.quit"));
+        let _ = self.icon.set_tooltip(Some(self.state.tooltip(locale)));
+        self.show(config);
+    }
+}
+
+struct NocapsApp {
+    controller: Arc<Controller>,
+    // The configuration on screen. Edits are made here and handed to the
+    // controller; changes from elsewhere replace it when their notice arrives.
+    config: Config,
+    running: Arc<AtomicBool>,
+    tray: Option<Tray>,
+    // Tray menu activations arrive on muda's event handler, which may run on a
+    // native callback rather than the egui frame. The handler parks events here
+    // and wakes the UI; `process_tray_events` drains them on the next frame.
+    tray_events: Arc<Mutex<Vec<MenuEvent>>>,
+    // Changes made by the file watcher or the control socket are announced on
+    // their threads and parked here the same way for `process_notices`.
+    notices: Arc<Mutex<Vec<Notice>>>,
+    // Backend failures and captured keys from the input threads. The runtime
+    // observer stores them without locking and has the repaint thread wake the
+    // UI; `process_runtime_events` takes them on the next frame.
+    runtime_reports: Arc<RuntimeReports>,
+    // Why a keyboard backend stopped, behind the tray's error icon. Cleared by
+    // the next change to the configuration in effect.
+    backend_error: Option<String>,
+    status: Option<Status>,
+    capturing: Option<Action>,
+    // Whether the window had focus at the start of this frame. Keys pressed
+    // in other windows are never taken for a capture.
+    focused: bool,
+    view: View,
+    layout: KeyboardLayout,
+    // The colours of `config.theme`, as last applied to the egui context.
+    palette: &'static Palette,
+    // The text in `config.language`, likewise.
+    locale: &'static Locale,
+    // Changes made from this window, for Ctrl+Z and Ctrl+Shift+Z.
+    history: History,
+    // Name typed into the profile bar for new, duplicated or renamed profiles.
+    profile_name: String,
+    #[cfg(target_os = "linux")]
+    autostart: Option<Installation>,
+    // The last permission check, shown by the setup view.
+    #[cfg(target_os = "linux")]
+    permissions: Vec<setup::Check>,
+    // Answered by the pkexec helper's thread when it is done.
+    #[cfg(target_os = "linux")]
+    installing: Option<std::sync::mpsc::Receiver<Result<(), String>>>,
+}
+
+/// What the central panel shows.
+#[derive(Clone, Copy, PartialEq)]
+enum View {
+    Bindings,
+    Keyboard,
+    Usage,
+    #[cfg(target_os = "linux")]
+    Inspector,
+    #[cfg(target_os = "linux")]
+    Setup,
+    #[cfg(target_os = "linux")]
+    Diagnostics,
+}
+
+struct Status {
+    is_error: bool,
+    message: String,
+}
+
+/// The one flag the runtime observer raises: something needs a new frame.
+const REPAINT: u32 = 1;
+
+/// What the keyboard backends reported for the window. Both are rare, one per
+/// failed worker or captured key, and never on the way of a remapped key.
+#[derive(Default)]
+struct RuntimeReports {
+    failures: ArcSwap<Vec<String>>,
+    captured: ArcSwapOption<(Option<InputKey>, String)>,
+}
+
+impl NocapsApp {
+    fn new(
+        controller: Arc<Controller>,
+        running: Arc<AtomicBool>,
+        startup_error: Option<String>,
+        context: &egui::Context,
+    ) -> Result<Self> {
+        let config = controller.config();
+        let palette = palette(config.theme);
+        apply_palette(context, palette);
+        let locale = locale(config.language);
+        configure_fonts(context, locale);
+        let tray_events: Arc<Mutex<Vec<MenuEvent>>> = Arc::new(Mutex::new(Vec::new()));
+        let (tray, tray_error) = match create_tray(&config, locale) {
+            Ok(tray) => {
+                // Forward tray activations onto our queue and wake the UI thread,
+                // so the egui loop can stay idle (no periodic polling) until the
+                // user actually clicks a menu item.
+                let queue = Arc::clone(&tray_events);
+                let context = context.clone();
+                MenuEvent::set_event_handler(Some(move |event| {
+                    if let Ok(mut queue) = queue.lock() {
+                        queue.push(event);
+                    }
+                    context.request_repaint();
+                }));
+                (Some(tray), None)
+            }
+            Err(error) => {
+                context.send_viewport_cmd(egui::ViewportCommand::Visible(true));
+                (None, Some(format!("{error:#}")))
+            }
+        };
+
+        // A backend that failed for want of permissions opens on the checklist
+        // that explains it.
+        #[cfg(target_os = "linux")]
+        let permissions = if startup_error.is_some() {
+            setup::check()
+        } else {
+            Vec::new()
+        };
+        #[cfg(target_os = "linux")]
+        let view = if permissions.iter().any(setup::Check::blocks_remapping) {
+            View::Setup
+        } else {
+            View::Bindings
+        };
+        #[cfg(not(target_os = "linux"))]
+        let view = View::Bindings;
+
+        let mut errors = Vec::new();
+        if let Some(message) = startup_error {
+            errors.push(locale.fill("startup.failed", &[("message", &message)]));
+        }
+        if let Some(message) = tray_error {
+            errors.push(locale.fill("startup.no_tray", &[("message", &message)]));
+        }
+
+        let notices: Arc<Mutex<Vec<Notice>>> = Arc::new(Mutex::new(Vec::new()));
+        let queue = Arc::clone(&notices);
+        let waker = context.clone();
+        controller.subscribe(move |notice| {
+            if let Ok(mut queue) = queue.lock() {
+                queue.push(notice.clone());
+            }
+            waker.request_repaint();
+        });
+
+        let runtime_reports = Arc::new(RuntimeReports::default());
+        let reports = Arc::clone(&runtime_reports);
+        let waker = context.clone();
+        let repaint = Handoff::spawn("nocaps-repaint", move |_| waker.request_repaint())?;
+        controller.runtime().observe(move |event| {
+            match event {
+                // Layer changes need no bookkeeping; the wake-up alone lets
+                // `update_tray_state` read the new state.
+                RuntimeEvent::Layer(_) => {}
+                RuntimeEvent::Failed(message) => {
+                    reports.failures.rcu(|failures| {
+                        let mut failures = Vec::clone(failures);
+                        failures.push(message.clone());
+                        failures
+                    });
+                }
+                RuntimeEvent::Captured { key, name } => {
+                    reports.captured.store(Some(Arc::new((key, name))));
+                }
+                _ => return,
+            }
+            repaint.raise(REPAINT);
+        });
+
+        Ok(Self {
+            controller,
+            config,
+            running,
+            tray,
+            tray_events,
+            notices,
+            runtime_reports,
+            backend_error: None,
+            status: (!errors.is_empty()).then(|| Status {
+                is_error: true,
+                message: errors.join("\n"),
+            }),
+            capturing: None,
+            focused: true,
+            view,
+            layout: KeyboardLayout::Ansi,
+            palette,
+            locale,
+            history: History::default(),
+            profile_name: String::new(),
+            #[cfg(target_os = "linux")]
+            autostart: Service::discover()
+                .ok()
+                .and_then(|service| service.installed()),
+            #[cfg(target_os = "linux")]
+            permissions,
+            #[cfg(target_os = "linux")]
+            installing: None,
+        })
+    }
+
+    fn process_tray_events(&mut self, context: &egui::Context) {
+        let Some(tray) = self.tray.as_ref() else {
+            return;
+        };
+        let enabled_id = tray.enabled.id().clone();
+        let configure_id = tray.configure.id().clone();
+        let quit_id = tray.quit.id().clone();
+        let profile_ids: Vec<(MenuId, String)> = tray
+            .profile_items
+            .iter()
+            .map(|(item, name)| (item.id().clone(), name.clone()))
+            .collect();
+        let events: Vec<MenuEvent> = match self.tray_events.lock() {
+            Ok(mut queue) => queue.drain(..).collect(),
+            Err(_) => return,
+        };
+        for event in events {
+            let toggle_enabled = event.id == enabled_id;
+            let configure = event.id == configure_id;
+            let quit = event.id == quit_id;
+            if toggle_enabled {
+                self.config.enabled = !self.config.enabled;
+                self.persist(Message::new(if self.config.enabled {
+                    "status.enabled"
+                } else {
+                    "status.disabled"
+                }));
+            } else if configure {
+                context.send_viewport_cmd(egui::ViewportCommand::Visible(true));
+                context.send_viewport_cmd(egui::ViewportCommand::Focus);
+            } else if quit {
+                self.running.store(false, Ordering::SeqCst);
+                context.send_viewport_cmd(egui::ViewportCommand::Close);
+            } else if let Some((_, name)) = profile_ids.iter().find(|(id, _)| event.id == *id) {
+                self.edit_profiles(|config| {
+                    config.switch_profile(name)?;
+                    Ok(Message::new("status.profile_active").with("profile", name.as_str()))
+                });
+            }
+        }
+    }
+
+    fn process_notices(&mut self, context: &egui::Context) {
+        let notices: Vec<Notice> = match self.notices.lock() {
+            Ok(mut queue) => queue.drain(..).collect(),
+            Err(_) => return,
+        };
+        for notice in notices {
+            self.status = Some(match notice {
+                Notice::ShowWindow => {
+                    context.send_viewport_cmd(egui::ViewportCommand::Visible(true));
+                    context.send_viewport_cmd(egui::ViewportCommand::Focus);
+                    continue;
+                }
+                Notice::Changed(message) => self.show_change(message),
+                Notice::Reloaded => {
+                    self.show_change(self.locale.text("status.reloaded").to_owned())
+                }
+                Notice::NotReloaded(error) => Status {
+                    is_error: true,
+                    message: self
+                        .locale
+                        .fill("status.not_reloaded", &[("error", &error)]),
+                },
+                // A change that could not be saved still applies, so show it.
+                Notice::Unsaved { change, error } => {
+                    self.show_config();
+                    Status {
+                        is_error: true,
+                        message: self
+                            .locale
+                            .fill("status.unsaved", &[("change", &change), ("error", &error)]),
+                    }
+                }
+            });
+        }
+    }
+
+    /// Shows a change that now applies; the backend has taken a
+    /// configuration since any error it reported.
+    fn show_change(&mut self, message: String) -> Status {
+        self.backend_error = None;
+        self.show_config();
+        Status {
+            is_error: false,
+            message,
+        }
+    }
+
+    /// Shows the configuration in effect in the window and the tray.
+    fn show_config(&mut self) {
+        self.config = self.controller.config();
+        if let Some(tray) = &mut self.tray {
+            tray.show(&self.config);
+        }
+    }
+
+    fn process_runtime_events(&mut self, context: &egui::Context) {
+        for message in self.runtime_reports.failures.swap(Arc::default()).iter() {
+            // Keys may no longer be remapped, so say so even if the window was hidden.
+            context.send_viewport_cmd(egui::ViewportCommand::Visible(true));
+            self.backend_error = Some(match self.backend_error.take() {
+                Some(previous) => format!("{previous}\n{message}"),
+                None => message.clone(),
+            });
+        }
+        if let Some(captured) = self.runtime_reports.captured.swap(None) {
+            let (key, name) = &*captured;
+            self.bind_captured(*key, name);
+        }
+    }
+
+    fn update_tray_state(&mut self) {
+        let state = if self.backend_error.is_some() {
+            TrayState::Error
+        } else if !self.config.enabled {
+            TrayState::Disabled
+        } else if self.controller.runtime().is_layer_active() {
+            TrayState::Layer
+        } else {
+            TrayState::Enabled
+        };
+        if let Some(tray) = &mut self.tray {
+            tray.set_state(state);
+        }
+    }
+
+    /// Starts or stops waiting for a key to bind to `action`. The backend is
+    /// only asked for the key while the window has focus.
+    fn set_capturing(&mut self, action: Option<Action>) {
+        let runtime = self.controller.runtime();
+        if action.is_some() && self.focused {
+            runtime.begin_capture();
+        } else if self.capturing.is_some() {
+            runtime.cancel_capture();
+        }
+        self.capturing = action;
+    }
+
+    /// Binds a key the keyboard backend read for a capture. `name` is the
+    /// backend's own name for it, for keys nocaps cannot bind.
+    fn bind_captured(&mut self, key: Option<InputKey>, name: &str) {
+        let Some(action) = self.capturing else {
+            return;
+        };
+        match key {
+            Some(key) => {
+                self.config.bind(action, key);
+                self.set_capturing(None);
+                self.persist(Self::bound_message(action, key));
+            }
+            None => {
+                // Wait for another key, as the window's own capture does.
+                if self.focused {
+                    self.controller.runtime().begin_capture();
+                }
+                self.status = Some(Status {
+                    is_error: true,
+                    message: self.locale.fill("status.unbindable", &[("key", name)]),
+                });
+            }
+        }
+    }
+
+    /// The fallback capture, from the window's own key events, for when the
+    /// keyboard backend cannot read keys itself. It only works while the
+    /// window has focus and cannot tell apart keys that egui merges, such as
+    /// the number row and the keypad.
+    fn capture_pressed_key(&mut self, context: &egui::Context) {
+        let Some(action) = self.capturing else {
+            return;
+        };
+        if self.controller.runtime().can_capture() {
+            return;
+        }
+        let pressed = context.input_mut(|input| {
+            let index = input.events.iter().position(|event| {
+                matches!(
+                    event,
+                    egui::Event::Key {
+                        pressed: true,
+                        repeat: false,
+                        ..
+                    }
+                )
+            })?;
+            match input.events.remove(index) {
+                egui::Event::Key {
+                    key, physical_key, ..
+                } => Some(physical_key.unwrap_or(key)),
+                _ => None,
+            }
+        });
+
+        let Some(pressed) = pressed else {
+            return;
+        };
+        match input_key_from_egui(pressed) {
+            Some(key) => {
+                self.config.bind(action, key);
+                self.set_capturing(None);
+                self.persist(Self::bound_message(action, key));
+            }
+            None => {
+                self.status = Some(Status {
+                    is_error: true,
+                    message: self.locale.text("status.unbindable_key").to_owned(),
+                });
+            }
+        }
+    }
+
+    fn bound_message(action: Action, key: InputKey) -> Message {
+        Message::new("status.bound")
+            .with("action", action)
+            .with("key", key)
+    }
+
+    fn cleared_message(action: Action) -> Message {
+        Message::new("status.cleared").with("action", action)
+    }
+
+    fn persist(&mut self, success_message: Message) {
+        // Announced in the language the edit leaves the window in, which
+        // differs from the current one when the edit switches languages.
+        let text = success_message.render(locale(self.config.language));
+        let before = self.controller.config();
+        let result = self.controller.apply(self.config.clone(), text.clone());
+        // A rejected edit leaves the previous bindings in effect; show those.
+        // One that could not be saved is in effect all the same, so it can be
+        // undone; a rejected one records nothing, as nothing changed.
+        self.config = self.controller.config();
+        self.history.record(&before, &self.config, success_message);
+        if let Some(tray) = &mut self.tray {
+            tray.show(&self.config);
+        }
+        self.status = Some(match result {
+            Ok(()) => Status {
+                is_error: false,
+                message: text,
+            },
+            Err(error) => Status {
+                is_error: true,
+                message: format!("{error:#}"),
+            },
+        });
+    }
+
+    /// Takes back the latest change made in the window, or with `redo` makes
+    /// the latest undone one again. Either way only the parts that change
+    /// touched are set on the configuration in effect, which is then applied
+    /// and saved like any other edit.
+    fn step_history(&mut self, redo: bool) {
+        let step = if redo {
+            self.history.redoable()
+        } else {
+            self.history.undoable()
+        };
+        let Some(step) = step else {
+            self.status = Some(Status {
+                is_error: false,
+                message: self
+                    .locale
+                    .text(if redo {
+                        "history.nothing_to_redo"
+                    } else {
+                        "history.nothing_to_undo"
+                    })
+                    .to_owned(),
+            });
+            return;
+        };
+        let message = self.locale.fill(
+            if redo {
+                "status.redone"
+            } else {
+                "status.undone"
+            },
+            &[("change", &step.message.render(self.locale))],
+        );
+        let before = self.controller.config();
+        let result = self.controller.update(|config| {
+            step.apply(config, redo);
+            Ok(message.clone())
+        });
+        // Taken even when it could not be saved, as it is in effect.
+        if self.controller.config() != before {
+            if redo {
+                self.history.redone();
+            } else {
+                self.history.undone();
+            }
+        }
+        self.config = self.controller.config();
+        self.set_capturing(None);
+        if let Some(tray) = &mut self.tray {
+            tray.show(&self.config);
+        }
+        self.status = Some(match result {
+            Ok(()) => Status {
+                is_error: false,
+                message,
+            },
+            Err(error) => Status {
+                is_error: true,
+                message: format!("{error:#}"),
+            },
+        });
+    }
+
+    /// Ctrl+Z and Ctrl+Shift+Z (Command on macOS), unless a key is being
+    /// captured or a text field has focus.
+    fn history_shortcuts(&mut self, context: &egui::Context) {
+        if self.capturing.is_some() || context.egui_wants_keyboard_input() {
+            return;
+        }
+        let undo = egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Z);
+        let redo = egui::KeyboardShortcut::new(
+            egui::Modifiers::COMMAND | egui::Modifiers::SHIFT,
+            egui::Key::Z,
+        );
+        // Checked first, since Ctrl+Z also matches with Shift held.
+        if context.input_mut(|input| input.consume_shortcut(&redo)) {
+            self.step_history(true);
+        } else if context.input_mut(|input| input.consume_shortcut(&undo)) {
+            self.step_history(false);
+        }
+    }
+
+    /// Undo, redo and a menu of recent changes, at the right of the status bar.
+    fn history_controls(&mut self, ui: &mut egui::Ui) {
+        let palette = self.palette;
+        let locale = self.locale;
+        let small = |text: &str, enabled: bool| {
+            let color = if enabled { palette.dim } else { palette.faint };
+            egui::Button::new(egui::RichText::new(text).color(color).size(12.0))
+        };
+        let redo_hover = self.history.redoable().map(|step| {
+            locale.fill(
+                "history.redo_hover",
+                &[("change", &step.message.render(locale))],
+            )
+        });
+        let undo_hover = self.history.undoable().map(|step| {
+            locale.fill(
+                "history.undo_hover",
+                &[("change", &step.message.render(locale))],
+            )
+        });
+
+        let mut steps: Option<(bool, usize)> = None;
+        let menu = ui.add(small(
+            locale.text("history.menu"),
+            undo_hover.is_some() || redo_hover.is_some(),
+        ));
+        egui::Popup::menu(&menu).show(|ui| {
+            let undone: Vec<String> = self
+                .history
+                .undone_steps()
+                .map(|step| step.message.render(locale))
+                .collect();
+            let done: Vec<String> = self
+                .history
+                .done()
+                .map(|step| step.message.render(locale))
+                .collect();
+            if undone.is_empty() && done.is_empty() {
+                ui.label(
+                    egui::RichText::new(locale.text("history.empty"))
+                        .color(palette.faint)
+                        .size(12.0),
+                );
+            }
+            // Listed newest at the top: what redo would bring back, then what
+            // undo would take away. Picking an entry steps all the way to it.
+            for (index, message) in undone.iter().enumerate().rev() {
+                let text = egui::RichText::new(format!("↷ {message}"))
+                    .color(palette.faint)
+                    .size(12.0);
+                if ui.button(text).clicked() {
+                    steps = Some((true, index + 1));
+                }
+            }
+            for (index, message) in done.iter().enumerate() {
+                let text = egui::RichText::new(format!("↶ {message}"))
+                    .color(palette.text)
+                    .size(12.0);
+                if ui.button(text).clicked() {
+                    steps = Some((false, index + 1));
+                }
+            }
+        });
+        if ui
+            .add(small("↷", redo_hover.is_some()))
+            .on_hover_text(
+                redo_hover.unwrap_or_else(|| locale.text("history.nothing_to_redo").to_owned()),
+            )
+            .clicked()
+        {
+            steps = Some((true, 1));
+        }
+        if ui
+            .add(small("↶", undo_hover.is_some()))
+            .on_hover_text(
+                undo_hover.unwrap_or_else(|| locale.text("history.nothing_to_undo").to_owned()),
+            )
+            .clicked()
+        {
+            steps = Some((false, 1));
+        }
+
+        if let Some((redo, count)) = steps {
+            for _ in 0..count {
+                let before = self.history.done().count();
+                self.step_history(redo);
+                // Stop at the first step that could not be applied.
+                if self.history.done().count() == before {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// The theme picker in the status bar. The choice is saved like any other
+    /// edit, and `follow_theme` recolours the window on the next frame.
+    fn theme_menu(&mut self, ui: &mut egui::Ui) {
+        let palette = self.palette;
+        let locale = self.locale;
+        let menu = ui
+            .add(egui::Button::new(
+                egui::RichText::new(locale.text("theme.menu"))
+                    .color(palette.dim)
+                    .size(12.0),
+            ))
+            .on_hover_text(locale.text("theme.hover"));
+        let mut chosen = None;
+        egui::Popup::menu(&menu).show(|ui| {
+            for theme in Theme::ALL {
+                let text = egui::RichText::new(locale.theme(*theme)).size(12.0);
+                if ui
+                    .selectable_label(self.config.theme == *theme, text)
+                    .clicked()
+                {
+                    chosen = Some(*theme);
+                }
+            }
+        });
+        if let Some(theme) = chosen.filter(|theme| *theme != self.config.theme) {
+            self.config.theme = theme;
+            self.persist(Message::new("status.theme").with("theme", theme));
+        }
+    }
+
+    /// The language picker beside it; `follow_language` switches the text.
+    fn language_menu(&mut self, ui: &mut egui::Ui) {
+        let palette = self.palette;
+        let locale = self.locale;
+        let menu = ui
+            .add(egui::Button::new(
+                egui::RichText::new(locale.text("language.menu"))
+                    .color(palette.dim)
+                    .size(12.0),
+            ))
+            .on_hover_text(locale.text("language.hover"));
+        let mut chosen = None;
+        egui::Popup::menu(&menu).show(|ui| {
+            for language in Language::ALL {
+                let text = egui::RichText::new(locale.language_name(*language)).size(12.0);
+                if ui
+                    .selectable_label(self.config.language == *language, text)
+                    .clicked()
+                {
+                    chosen = Some(*language);
+                }
+            }
+        });
+        if let Some(language) = chosen.filter(|language| *language != self.config.language) {
+            self.config.language = language;
+            self.persist(Message::new("status.language").with("language", language));
+        }
+    }
+
+    /// Recolours the window when the theme changed, whether from the picker or
+    /// in the configuration file.
+    fn follow_theme(&mut self, context: &egui::Context) {
+        let wanted = palette(self.config.theme);
+        if !std::ptr::eq(wanted, self.palette) {
+            self.palette = wanted;
+            apply_palette(context, wanted);
+        }
+    }
+
+    /// Switches the window and tray to another language when it changed,
+    /// whether from the menu or in the configuration file.
+    fn follow_language(&mut self, context: &egui::Context) {
+        let wanted = locale(self.config.language);
+        if !std::ptr::eq(wanted, self.locale) {
+            self.locale = wanted;
+            configure_fonts(context, wanted);
+            if let Some(tray) = &mut self.tray {
+                tray.relabel(wanted, &self.config);
+            }
+        }
+    }
+
+    /// Applies a profile operation to the on-screen configuration and persists
+    /// it. Returns false, with the reason in the status bar, when it is refused.
+    fn edit_profiles(&mut self, change: impl FnOnce(&mut Config) -> Result<Message>) -> bool {
+        let mut config = self.config.clone();
+        match change(&mut config) {
+            Ok(message) => {
+                self.config = config;
+                self.set_capturing(None);
+                self.persist(message);
+                true
+            }
+            Err(error) => {
+                self.status = Some(Status {
+                    is_error: true,
+                    message: match error.downcast_ref::<ProfileError>() {
+                        Some(error) => Message::from(error).render(self.locale),
+                        None => format!("{error:#}"),
+                    },
+                });
+                false
+            }
+        }
+    }
+
+    /// Installs or removes the login entry. The window always installs the
+    /// windowed mode; `nocaps service install --headless` covers the rest.
+    #[cfg(target_os = "linux")]
+    fn toggle_autostart(&mut self) {
+        let locale = self.locale;
+        let installed = self.autostart.is_some();
+        let result = Service::discover().and_then(|service| {
+            if installed {
+                service.uninstall().map(|_| None)
+            } else {
+                service.install(false).map(Some)
+            }
+        });
+        self.status = Some(match result {
+            Ok(autostart) => {
+                let message = match &autostart {
+                    Some(installation) => locale.fill(
+                        "status.autostart_installed",
+                        &[("installation", &installation.to_string())],
+                    ),
+                    None => locale.text("status.autostart_removed").to_owned(),
+                };
+                self.autostart = autostart;
+                Status {
+                    is_error: false,
+                    message,
+                }
+            }
+            Err(error) => Status {
+                is_error: true,
+                message: locale.fill(
+                    "status.autostart_failed",
+                    &[("error", &format!("{error:#}"))],
+                ),
+            },
+        });
+    }
+
+    fn action_row(&mut self, ui: &mut egui::Ui, action: Action) {
+        let palette = self.palette;
+        let locale = self.locale;
+        let selected = self.capturing == Some(action);
+        let binding = self.config.key_for(action);
+        ui.horizontal(|ui| {
+            ui.set_min_height(20.0);
+            let label_color = if selected {
+                palette.accent
+            } else {
+                palette.dim
+            };
+            ui.label(
+                egui::RichText::new(locale.action(action))
+                    .color(label_color)
+                    .size(12.0),
+            );
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                // Clear is a single glyph, not a word — the row reads like a
+                // terminal field you blank out, and stays tight. It is only
+                // hidden when there is nothing to clear, so every keycap lines
+                // up and keeps its id, and with it keyboard focus, when the
+                // binding changes. Delete on the keycap does the same from the
+                // keyboard, so Tab skips the glyph.
+                let clear = ui.add_visible(
+                    binding.is_some(),
+                    egui::Button::new(egui::RichText::new("×").color(palette.dim).size(15.0))
+                        .frame(false)
+                        .sense(egui::Sense::CLICK),
+                );
+                clear.widget_info(|| {
+                    egui::WidgetInfo::labeled(
+                        egui::WidgetType::Button,
+                        binding.is_some(),
+                        locale.fill("slot.clear", &[("action", locale.action(action))]),
+                    )
+                });
+                let mut cleared = binding.is_some() && clear.clicked();
+                if binding.is_some() {
+                    clear.on_hover_text(locale.text("slot.clear_hover"));
+                }
+
+                let cap_text = if selected {
+                    "[?]".to_owned()
+                } else if let Some(key) = binding {
+                    locale.key(key).to_owned()
+                } else {
+                    "·".to_owned()
+                };
+                let cap_color = if selected {
+                    palette.accent
+                } else if binding.is_some() {
+                    palette.text
+                } else {
+                    palette.faint
+                };
+                let keycap = ui.add(
+                    egui::Button::new(egui::RichText::new(cap_text).color(cap_color).size(12.0))
+                        .fill(if selected {
+                            palette.keycap_hot
+                        } else {
+                            palette.screen
+                        })
+                        .stroke(egui::Stroke::new(
+                            1.0,
+                            if selected {
+                                palette.accent
+                            } else {
+                                palette.faint
+                            },
+                        ))
+                        .min_size(egui::vec2(48.0, 18.0)),
+                );
+                keycap.widget_info(|| {
+                    let state = match binding {
+                        _ if selected => locale.text("slot.capturing").to_owned(),
+                        Some(key) => locale.fill("slot.bound", &[("key", locale.key(key))]),
+                        None => locale.text("slot.unbound").to_owned(),
+                    };
+                    egui::WidgetInfo::labeled(
+                        egui::WidgetType::Button,
+                        true,
+                        format!("{}: {state}", locale.action(action)),
+                    )
+                });
+                if keycap.has_focus() {
+                    ui.painter().rect_stroke(
+                        keycap.rect,
+                        0.0,
+                        egui::Stroke::new(1.0, palette.accent),
+                        egui::StrokeKind::Inside,
+                    );
+                    // While capturing, Tab, the arrows and Escape belong to
+                    // the capture rather than moving focus off the slot.
+                    let filter = egui::EventFilter {
+                        tab: selected,
+                        horizontal_arrows: selected,
+                        vertical_arrows: selected,
+                        escape: selected,
+                    };
+                    ui.memory_mut(|memory| memory.set_focus_lock_filter(keycap.id, filter));
+                    if !selected && binding.is_some() {
+                        cleared |= ui.input_mut(|input| {
+                            input.consume_key(egui::Modifiers::NONE, egui::Key::Delete)
+                                || input.consume_key(egui::Modifiers::NONE, egui::Key::Backspace)
+                        });
+                    }
+                }
+                // Enter and Space click the focused keycap too.
+                if keycap.clicked() {
+                    if selected {
+                        self.set_capturing(None);
+                    } else {
+                        self.set_capturing(Some(action));
+                        self.status = None;
+                        keycap.request_focus();
+                    }
+                }
+                if cleared {
+                    self.config.unbind(action);
+                    self.set_capturing(None);
+                    self.persist(Self::cleared_message(action));
+                }
+            });
+        });
+    }
+
+    fn category_block(&mut self, ui: &mut egui::Ui, category: Category) {
+        let palette = self.palette;
+        let title = self.locale.category(category);
+        egui::Frame::new()
+            .fill(palette.panel)
+            .stroke(egui::Stroke::new(1.0, palette.faint))
+            .corner_radius(0)
+            .inner_margin(egui::Margin::symmetric(8, 5))
+            .show(ui, |ui| {
+                ui.set_width(ui.available_width());
+                ui.label(
+                    egui::RichText::new(format!("[ {} ]", title.to_uppercase()))
+                        .color(palette.accent)
+                        .size(12.0)
+                        .strong(),
+                );
+                ui.add_space(3.0);
+                for action in Action::ALL
+                    .iter()
+                    .copied()
+                    .filter(|action| action.category() == category)
+                {
+                    self.action_row(ui, action);
+                }
+            });
+        ui.add_space(7.0);
+    }
+
+    /// A title-bar button that opens `view`, or goes back to the bindings
+    /// when it is already open.
+    fn view_button(&mut self, ui: &mut egui::Ui, view: View, text: &str, hover: &str) {
+        let palette = self.palette;
+        let open = self.view == view;
+        let (color, hover) = if open {
+            (palette.accent, self.locale.text("title.back_hover"))
+        } else {
+            (palette.dim, hover)
+        };
+        if ui
+            .add(egui::Button::new(
+                egui::RichText::new(text).color(color).size(12.0),
+            ))
+            .on_hover_text(hover)
+            .clicked()
+        {
+            self.set_view(if open { View::Bindings } else { view });
+        }
+    }
+
+    fn set_view(&mut self, view: View) {
+        // The inspector only watches keys while someone is looking at it.
+        #[cfg(target_os = "linux")]
+        self.controller
+            .runtime()
+            .inspector()
+            .set_enabled(view == View::Inspector);
+        #[cfg(target_os = "linux")]
+        if view == View::Setup {
+            self.permissions = setup::check();
+        }
+        self.view = view;
+        self.set_capturing(None);
+    }
+
+    fn layout_switch(&mut self, ui: &mut egui::Ui) {
+        let palette = self.palette;
+        for layout in [KeyboardLayout::Iso, KeyboardLayout::Ansi] {
+            let (fg, bg) = if self.layout == layout {
+                (palette.screen, palette.text)
+            } else {
+                (palette.dim, palette.panel)
+            };
+            let chip = egui::Button::new(egui::RichText::new(layout.name()).color(fg).size(12.0))
+                .fill(bg)
+                .stroke(egui::Stroke::new(1.0, palette.faint));
+            if ui.add(chip).clicked() {
+                self.layout = layout;
+            }
+        }
+    }
+
+    /// The Caps Lock layer drawn on a keyboard. Each keycap shows the action it
+    /// invokes; clicking one picks an action for it, and actions from the
+    /// palette below can be dropped onto any key. Free keys stand out.
+    fn keyboard_view(&mut self, ui: &mut egui::Ui) {
+        let palette = self.palette;
+        let locale = self.locale;
+        ui.horizontal(|ui| {
+            ui.label(
+                egui::RichText::new(format!("[ {} ]", locale.text("keys.heading")))
+                    .color(palette.accent)
+                    .size(12.0)
+                    .strong(),
+            );
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                self.layout_switch(ui);
+            });
+        });
+        ui.add_space(4.0);
+
+        let bound: std::collections::HashMap<InputKey, Action> = self
+            .config
+            .bindings()
+            .iter()
+            .map(|(action, key)| (*key, *action))
+            .collect();
+        let toggle = self.config.toggle.map(|toggle| toggle.key);
+        let height = (ui.available_width() / 15.0).min(40.0);
+        // `None` as the action clears the key.
+        let mut edit: Option<(InputKey, Option<Action>)> = None;
+        draw_keyboard(ui, palette, self.layout, height, |ui, key, size| {
+            let action = bound.get(&key).copied();
+            let reserved = toggle == Some(key);
+            let sense = if reserved {
+                egui::Sense::hover()
+            } else {
+                egui::Sense::click()
+            };
+            let (rect, response) = ui.allocate_exact_size(size, sense);
+            let dropping = !reserved && response.dnd_hover_payload::<Action>().is_some();
+            let rect = rect.shrink(1.5);
+            let (fill, stroke) = if dropping {
+                (palette.keycap_hot, palette.accent)
+            } else if reserved || action.is_some() {
+                (palette.panel, palette.faint)
+            } else if response.hovered() {
+                (palette.free_key, palette.accent)
+            } else {
+                (palette.free_key, palette.dim)
+            };
+            let painter = ui.painter();
+            painter.rect_filled(rect, 0.0, fill);
+            painter.rect_stroke(
+                rect,
+                0.0,
+                egui::Stroke::new(1.0, stroke),
+                egui::StrokeKind::Inside,
+            );
+            painter.text(
+                rect.left_top() + egui::vec2(3.0, 2.0),
+                egui::Align2::LEFT_TOP,
+                key_legend(locale, key),
+                egui::FontId::monospace(9.0),
+                if action.is_some() {
+                    palette.dim
+                } else {
+                    palette.text
+                },
+            );
+            let (legend, color) = match action {
+                _ if reserved => (locale.text("keys.toggle"), palette.faint),
+                Some(action) => (action_legend(locale, action), palette.accent),
+                None => ("", palette.text),
+            };
+            painter.text(
+                rect.center_bottom() - egui::vec2(0.0, 3.0),
+                egui::Align2::CENTER_BOTTOM,
+                legend,
+                egui::FontId::monospace(10.0),
+                color,
+            );
+
+            if reserved {
+                let hover = locale.fill("keys.reserved", &[("key", locale.key(key))]);
+                response.widget_info(|| {
+                    egui::WidgetInfo::labeled(egui::WidgetType::Label, true, &hover)
+                });
+                response.on_hover_text(hover);
+                return;
+            }
+            if let Some(dropped) = response.dnd_release_payload::<Action>() {
+                edit = Some((key, Some(*dropped)));
+            }
+            if response.has_focus() {
+                painter.rect_stroke(
+                    rect,
+                    0.0,
+                    egui::Stroke::new(1.0, palette.accent),
+                    egui::StrokeKind::Inside,
+                );
+                if action.is_some()
+                    && ui.input_mut(|input| {
+                        input.consume_key(egui::Modifiers::NONE, egui::Key::Delete)
+                            || input.consume_key(egui::Modifiers::NONE, egui::Key::Backspace)
+                    })
+                {
+                    edit = Some((key, None));
+                }
+            }
+            let hover = match action {
+                Some(action) => locale.fill(
+                    "keys.bound",
+                    &[("key", locale.key(key)), ("action", locale.action(action))],
+                ),
+                None => locale.fill("keys.free", &[("key", locale.key(key))]),
+            };
+            response
+                .widget_info(|| egui::WidgetInfo::labeled(egui::WidgetType::Button, true, &hover));
+            let response = response.on_hover_text(hover);
+            egui::Popup::menu(&response).show(|ui| {
+                if action.is_some()
+                    && ui
+                        .button(egui::RichText::new(locale.text("keys.clear")).color(palette.dim))
+                        .clicked()
+                {
+                    edit = Some((key, None));
+                }
+                for category in Category::ALL.iter().copied() {
+                    ui.label(
+                        egui::RichText::new(locale.category(category).to_uppercase())
+                            .color(palette.faint)
+                            .size(10.0),
+                    );
+                    for choice in Action::ALL.iter().copied() {
+                        if choice.category() != category {
+                            continue;
+                        }
+                        let color = if Some(choice) == action {
+                            palette.accent
+                        } else {
+                            palette.text
+                        };
+                        if ui
+                            .button(
+                                egui::RichText::new(locale.action(choice))
+                                    .color(color)
+                                    .size(12.0),
+                            )
+                            .clicked()
+                        {
+                            edit = Some((key, Some(choice)));
+                        }
+                    }
+                }
+            });
+        });
+
+        ui.add_space(8.0);
+        ui.label(
+            egui::RichText::new(locale.text("keys.hint"))
+                .color(palette.faint)
+                .size(11.0),
+        );
+        ui.add_space(2.0);
+        ui.horizontal_wrapped(|ui| {
+            for action in Action::ALL.iter().copied() {
+                let color = if self.config.key_for(action).is_some() {
+                    palette.dim
+                } else {
+                    palette.text
+                };
+                let id = egui::Id::new(("nocaps-palette", action));
+                ui.dnd_drag_source(id, action, |ui| {
+                    egui::Frame::new()
+                        .fill(palette.panel)
+                        .stroke(egui::Stroke::new(1.0, palette.faint))
+                        .inner_margin(egui::Margin::symmetric(5, 1))
+                        .show(ui, |ui| {
+                            ui.label(
+                                egui::RichText::new(locale.action(action))
+                                    .color(color)
+                                    .size(11.0),
+                            );
+                        });
+                });
+            }
+        });
+
+        match edit {
+            Some((key, Some(action))) => {
+                self.config.bind(action, key);
+                self.persist(Self::bound_message(action, key));
+            }
+            Some((key, None)) => {
+                if let Some(action) = bound.get(&key) {
+                    self.config.unbind(*action);
+                    self.persist(Self::cleared_message(*action));
+                }
+            }
+            None => {}
+        }
+    }
+
+    fn usage_view(&mut self, ui: &mut egui::Ui) {
+        let palette = self.palette;
+        let locale = self.locale;
+        let counts = self.controller.runtime().usage().counts();
+        ui.horizontal(|ui| {
+            ui.label(
+                egui::RichText::new(format!("[ {} ]", locale.text("usage.heading")))
+                    .color(palette.accent)
+                    .size(12.0)
+                    .strong(),
+            );
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui
+                    .add(egui::Button::new(
+                        egui::RichText::new(locale.text("usage.reset"))
+                            .color(palette.dim)
+                            .size(12.0),
+                    ))
+                    .on_hover_text(locale.text("usage.reset_hover"))
+                    .clicked()
+                {
+                    self.controller.runtime().usage().reset();
+                    self.status = Some(Status {
+                        is_error: false,
+                        message: locale.text("status.usage_reset").to_owned(),
+                    });
+                }
+                let (text, fg, bg) = if self.config.statistics {
+                    (
+                        locale.text("usage.counting"),
+                        palette.screen,
+                        palette.accent,
+                    )
+                } else {
+                    (
+                        locale.text("usage.not_counting"),
+                        palette.dim,
+                        palette.panel,
+                    )
+                };
+                if ui
+                    .add(egui::Button::new(egui::RichText::new(text).color(fg).size(12.0)).fill(bg))
+                    .clicked()
+                {
+                    self.config.statistics = !self.config.statistics;
+                    self.persist(Message::new(if self.config.statistics {
+                        "status.usage_on"
+                    } else {
+                        "status.usage_off"
+                    }));
+                }
+            });
+        });
+        ui.label(
+            egui::RichText::new(locale.text("usage.privacy"))
+                .color(palette.faint)
+                .size(11.0),
+        );
+        ui.add_space(6.0);
+        draw_heatmap(ui, palette, locale, self.layout, &counts);
+        ui.add_space(10.0);
+
+        let mut actions: Vec<(Action, u64)> = counts.actions.into_iter().collect();
+        actions.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        if actions.is_empty() {
+            ui.label(
+                egui::RichText::new(locale.text("usage.empty"))
+                    .color(palette.dim)
+                    .size(12.0),
+            );
+            return;
+        }
+        let rows = actions.len().div_ceil(2);
+        ui.columns(2, |columns| {
+            for (column, actions) in columns.iter_mut().zip(actions.chunks(rows)) {
+                for (action, count) in actions {
+                    column.horizontal(|ui| {
+                        ui.label(
+                            egui::RichText::new(locale.action(*action))
+                                .color(palette.dim)
+                                .size(12.0),
+                        );
+                        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                            ui.label(egui::RichText::new(count.to_string()).size(12.0));
+                        });
+                    });
+                }
+            }
+        });
+    }
+
+    #[cfg(target_os = "linux")]
+    fn inspector_view(&mut self, ui: &mut egui::Ui) {
+        let palette = self.palette;
+        let locale = self.locale;
+        let inspector = self.controller.runtime().inspector();
+        ui.horizontal(|ui| {
+            ui.label(
+                egui::RichText::new(format!("[ {} ]", locale.text("inspector.heading")))
+                    .color(palette.alarm)
+                    .size(12.0)
+                    .strong(),
+            );
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui
+                    .add(egui::Button::new(
+                        egui::RichText::new(locale.text("inspector.clear"))
+                            .color(palette.dim)
+                            .size(12.0),
+                    ))
+                    .clicked()
+                {
+                    inspector.clear();
+                }
+            });
+        });
+        ui.label(
+            egui::RichText::new(locale.text("inspector.warning"))
+                .color(palette.alarm)
+                .size(11.0),
+        );
+        ui.add_space(6.0);
+
+        let events = inspector.recent();
+        if events.is_empty() {
+            ui.label(
+                egui::RichText::new(locale.text("inspector.waiting"))
+                    .color(palette.dim)
+                    .size(12.0),
+            );
+            return;
+        }
+        let cell = |ui: &mut egui::Ui, text: String, color: egui::Color32| {
+            ui.label(egui::RichText::new(text).color(color).size(11.0));
+        };
+        egui::ScrollArea::vertical()
+            .auto_shrink([false, false])
+            .show(ui, |ui| {
+                egui::Grid::new("nocaps-inspector")
+                    .num_columns(6)
+                    .spacing(egui::vec2(10.0, 2.0))
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for heading in [
+                            "inspector.device",
+                            "inspector.raw",
+                            "inspector.key",
+                            "",
+                            "inspector.layer",
+                            "inspector.output",
+                        ] {
+                            let heading = if heading.is_empty() {
+                                ""
+                            } else {
+                                locale.text(heading)
+                            };
+                            cell(ui, heading.to_owned(), palette.faint);
+                        }
+                        ui.end_row();
+                        for event in events {
+                            cell(ui, event.device, palette.dim);
+                            cell(
+                                ui,
+                                format!(
+                                    "{} ({})",
+                                    crate::platform::key_code_name(event.code),
+                                    event.code
+                                ),
+                                palette.text,
+                            );
+                            cell(
+                                ui,
+                                event.key.map_or("-", |key| locale.key(key)).to_owned(),
+                                palette.text,
+                            );
+                            let value = locale.text(match event.value {
+                                0 => "inspector.up",
+                                1 => "inspector.down",
+                                _ => "inspector.repeat",
+                            });
+                            cell(ui, value.to_owned(), palette.dim);
+                            let (layer, color) = if event.layer {
+                                (locale.text("inspector.caps"), palette.accent)
+                            } else {
+                                ("-", palette.faint)
+                            };
+                            cell(ui, layer.to_owned(), color);
+                            let (output, color) = match event.output {
+                                Output::Emitted(code) if code == event.code => (
+                                    locale.text("inspector.passed_through").to_owned(),
+                                    palette.dim,
+                                ),
+                                Output::Emitted(code) => {
+                                    (crate::platform::key_code_name(code), palette.accent)
+                                }
+                                Output::Swallowed => {
+                                    (locale.text("inspector.swallowed").to_owned(), palette.dim)
+                                }
+                                Output::Ungrabbed => (
+                                    locale.text("inspector.not_grabbed").to_owned(),
+                                    palette.faint,
+                                ),
+                            };
+                            cell(ui, output, color);
+                            ui.end_row();
+                        }
+                    });
+            });
+    }
+
+    fn title_bar(&mut self, ui: &mut egui::Ui) {
+        let palette = self.palette;
+        let locale = self.locale;
+        ui.horizontal(|ui| {
+            // Reverse-video brand, the way a DOS app stamps its top line.
+            egui::Frame::new()
+                .fill(palette.text)
+                .corner_radius(0)
+                .inner_margin(egui::Margin::symmetric(7, 2))
+                .show(ui, |ui| {
+                    ui.label(
+                        egui::RichText::new("nocaps")
+                            .color(palette.screen)
+                            .strong()
+                            .size(16.0),
+                    );
+                });
+            ui.label(
+                egui::RichText::new(concat!("v", env!("CARGO_PKG_VERSION")))
+                    .color(palette.faint)
+                    .size(12.0),
+            )
+            .on_hover_text(locale.fill(
+                "title.config",
+                &[(
+                    "path",
+                    &self.controller.store().path().display().to_string(),
+                )],
+            ));
+
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                let enabled = self.config.enabled;
+                let (text, fg, bg) = if enabled {
+                    (locale.text("title.on"), palette.screen, palette.accent)
+                } else {
+                    (locale.text("title.off"), palette.dim, palette.panel)
+                };
+                let toggle = ui.add(
+                    egui::Button::new(egui::RichText::new(text).color(fg).strong().size(13.0))
+                        .fill(bg)
+                        .stroke(egui::Stroke::new(
+                            1.0,
+                            if enabled {
+                                palette.accent
+                            } else {
+                                palette.faint
+                            },
+                        ))
+                        .min_size(egui::vec2(98.0, 20.0)),
+                );
+                if toggle.clicked() {
+                    self.config.enabled = !self.config.enabled;
+                    self.persist(Message::new(if self.config.enabled {
+                        "status.enabled"
+                    } else {
+                        "status.disabled"
+                    }));
+                }
+
+                #[cfg(target_os = "linux")]
+                self.view_button(
+                    ui,
+                    View::Setup,
+                    locale.text("title.setup"),
+                    locale.text("title.setup_hover"),
+                );
+                #[cfg(target_os = "linux")]
+                self.view_button(
+                    ui,
+                    View::Diagnostics,
+                    locale.text("title.diagnostics"),
+                    locale.text("title.diagnostics_hover"),
+                );
+                #[cfg(target_os = "linux")]
+                self.view_button(
+                    ui,
+                    View::Inspector,
+                    locale.text("title.debug"),
+                    locale.text("title.debug_hover"),
+                );
+                self.view_button(
+                    ui,
+                    View::Usage,
+                    locale.text("title.stats"),
+                    locale.text("title.stats_hover"),
+                );
+                self.view_button(
+                    ui,
+                    View::Keyboard,
+                    locale.text("title.keys"),
+                    locale.text("title.keys_hover"),
+                );
+
+                if ui
+                    .add(egui::Button::new(
+                        egui::RichText::new(locale.text("title.restore"))
+                            .color(palette.dim)
+                            .size(12.0),
+                    ))
+                    .on_hover_text(locale.text("title.restore_hover"))
+                    .clicked()
+                {
+                    self.config.restore_default_bindings();
+                    self.set_capturing(None);
+                    self.persist(
+                        Message::new("status.restored")
+                            .with("profile", self.config.profile.as_str()),
+                    );
+                }
+
+                #[cfg(target_os = "linux")]
+                {
+                    let (color, hover) = match &self.autostart {
+                        Some(installation) => {
+                            let installation = installation.to_string();
+                            (
+                                palette.accent,
+                                locale.fill(
+                                    "title.autostart_on_hover",
+                                    &[("installation", &installation)],
+                                ),
+                            )
+                        }
+                        None => (
+                            palette.dim,
+                            locale.text("title.autostart_off_hover").to_owned(),
+                        ),
+                    };
+                    if ui
+                        .add(egui::Button::new(
+                            egui::RichText::new(locale.text("title.autostart"))
+                                .color(color)
+                                .size(12.0),
+                        ))
+                        .on_hover_text(hover)
+                        .clicked()
+                    {
+                        self.toggle_autostart();
+                    }
+                }
+
+                if self.tray.is_none()
+                    && ui
+                        .add(egui::Button::new(
+                            egui::RichText::new(locale.text("title.quit"))
+                                .color(palette.dim)
+                                .size(12.0),
+                        ))
+                        .clicked()
+                {
+                    self.running.store(false, Ordering::SeqCst);
+                    ui.ctx().send_viewport_cmd(egui::ViewportCommand::Close);
+                }
+            });
+        });
+    }
+
+    fn profile_bar(&mut self, ui: &mut egui::Ui) {
+        let palette = self.palette;
+        let locale = self.locale;
+        let small =
+            |text: &str| egui::Button::new(egui::RichText::new(text).color(palette.dim).size(12.0));
+        ui.horizontal(|ui| {
+            ui.label(
+                egui::RichText::new(locale.text("profile.heading"))
+                    .color(palette.faint)
+                    .size(12.0),
+            );
+            let names: Vec<String> = self.config.profiles.keys().cloned().collect();
+            for name in names {
+                let active = name == self.config.profile;
+                let (fg, bg) = if active {
+                    (palette.screen, palette.text)
+                } else {
+                    (palette.dim, palette.panel)
+                };
+                let chip = egui::Button::new(egui::RichText::new(&name).color(fg).size(12.0))
+                    .fill(bg)
+                    .stroke(egui::Stroke::new(1.0, palette.faint));
+                if ui.add(chip).clicked() && !active {
+                    self.edit_profiles(|config| {
+                        config.switch_profile(&name)?;
+                        Ok(Message::new("status.profile_active").with("profile", name.as_str()))
+                    });
+                }
+            }
+
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                let current = self.config.profile.clone();
+                let typed = self.profile_name.trim().to_owned();
+                let done = if ui
+                    .add(small(locale.text("profile.delete")))
+                    .on_hover_text(locale.fill("profile.delete_hover", &[("profile", &current)]))
+                    .clicked()
+                {
+                    self.edit_profiles(|config| {
+                        config.delete_profile()?;
+                        Ok(
+                            Message::new("status.profile_deleted")
+                                .with("profile", current.as_str()),
+                        )
+                    })
+                } else if ui
+                    .add(small(locale.text("profile.rename")))
+                    .on_hover_text(locale.fill("profile.rename_hover", &[("profile", &current)]))
+                    .clicked()
+                {
+                    self.edit_profiles(|config| {
+                        config.rename_profile(&typed)?;
+                        Ok(Message::new("status.profile_renamed")
+                            .with("profile", current.as_str())
+                            .with("name", typed.as_str()))
+                    })
+                } else if ui
+                    .add(small(locale.text("profile.copy")))
+                    .on_hover_text(locale.fill("profile.copy_hover", &[("profile", &current)]))
+                    .clicked()
+                {
+                    self.edit_profiles(|config| {
+                        config.duplicate_profile(&typed)?;
+                        Ok(Message::new("status.profile_copied")
+                            .with("profile", current.as_str())
+                            .with("name", typed.as_str()))
+                    })
+                } else if ui
+                    .add(small(locale.text("profile.new")))
+                    .on_hover_text(locale.text("profile.new_hover"))
+                    .clicked()
+                {
+                    self.edit_profiles(|config| {
+                        config.create_profile(&typed)?;
+                        Ok(Message::new("status.profile_created").with("name", typed.as_str()))
+                    })
+                } else {
+                    false
+                };
+                if done {
+                    self.profile_name.clear();
+                }
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.profile_name)
+                        .hint_text(locale.text("profile.name_hint"))
+                        .desired_width(110.0),
+                );
+            });
+        });
+    }
+
+    fn status_bar(&mut self, ui: &mut egui::Ui) {
+        let palette = self.palette;
+        let locale = self.locale;
+        let cursor = if ui.input(|input| input.time).rem_euclid(1.0) < 0.5 {
+            "_"
+        } else {
+            " "
+        };
+        let (text, color) = if let Some(action) = self.capturing {
+            (
+                format!(
+                    "> {}",
+                    locale.fill(
+                        "status.capturing",
+                        &[
+                            ("action", &locale.action(action).to_uppercase()),
+                            ("cursor", cursor),
+                        ],
+                    )
+                ),
+                palette.accent,
+            )
+        } else if let Some(error) = &self.backend_error {
+            (format!("! {}", error.replace('\n', "  ")), palette.alarm)
+        } else if let Some(status) = &self.status {
+            (
+                format!("> {}", status.message.replace('\n', "  ")),
+                if status.is_error {
+                    palette.alarm
+                } else {
+                    palette.text
+                },
+            )
+        } else {
+            (format!("> {}", locale.text("status.ready")), palette.dim)
+        };
+        ui.horizontal(|ui| {
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                self.history_controls(ui);
+                self.theme_menu(ui);
+                self.language_menu(ui);
+                ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui| {
+                    let text = egui::RichText::new(text).color(color).size(13.0);
+                    ui.add(egui::Label::new(text).truncate());
+                });
+            });
+        });
+    }
+}
+
+impl eframe::App for NocapsApp {
+    fn logic(&mut self, context: &egui::Context, _frame: &mut eframe::Frame) {
+        pump_native_tray_events();
+        // Losing focus ends a capture, so a key typed into another window
+        // is neither taken from it nor bound.
+        self.focused = context.input(|input| input.viewport().focused) != Some(false);
+        if !self.focused {
+            self.set_capturing(None);
+        }
+        self.process_tray_events(context);
+        self.process_notices(context);
+        self.process_runtime_events(context);
+        self.update_tray_state();
+        self.capture_pressed_key(context);
+        self.history_shortcuts(context);
+        self.follow_theme(context);
+        self.follow_language(context);
+        #[cfg(target_os = "linux")]
+        self.finish_installing();
+
+        if context.input(|input| input.viewport().close_requested())
+            && self.running.load(Ordering::SeqCst)
+        {
+            if self.tray.is_some() {
+                context.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+                context.send_viewport_cmd(egui::ViewportCommand::Visible(false));
+                // A hidden window must not keep taking the next key pressed.
+                self.set_capturing(None);
+                #[cfg(target_os = "linux")]
+                if self.view == View::Inspector {
+                    self.set_view(View::Bindings);
+                }
+            } else {
+                self.running.store(false, Ordering::SeqCst);
+            }
+        }
+
+        // Tray clicks wake the UI via the muda event handler, and egui already
+        // repaints on its own window input, so no periodic repaint is needed to
+        // stay responsive while idle. Linux is the exception: its AppIndicator
+        // menu only emits events while the GTK loop is iterated by
+        // `pump_native_tray_events`, so keep a gentle tick to drive that pump.
+        #[cfg(target_os = "linux")]
+        context.request_repaint_after(std::time::Duration::from_millis(100));
+
+        // The only animation is the status-bar cursor while waiting for a key,
+        // so we wake just for that — the window otherwise stays idle at rest.
+        if self.capturing.is_some() {
+            context.request_repaint_after(std::time::Duration::from_millis(450));
+        }
+        // Counts and inspected events change on the input threads without
+        // waking the window.
+        match self.view {
+            View::Bindings | View::Keyboard => {}
+            #[cfg(target_os = "linux")]
+            View::Setup => {}
+            // Worker states change on the input threads.
+            #[cfg(target_os = "linux")]
+            View::Diagnostics => {
+                context.request_repaint_after(std::time::Duration::from_millis(500))
+            }
+            View::Usage => context.request_repaint_after(std::time::Duration::from_secs(1)),
+            #[cfg(target_os = "linux")]
+            View::Inspector => context.request_repaint_after(std::time::Duration::from_millis(100)),
+        }
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, _frame: &mut eframe::Frame) {
+        let palette = self.palette;
+        ui.visuals_mut().override_text_color = Some(palette.text);
+        let screen = ui.max_rect();
+        ui.painter().rect_filled(screen, 0.0, palette.screen);
+        draw_scanlines(ui.painter(), palette, screen);
+
+        let bar = || {
+            egui::Frame::new()
+                .fill(palette.panel)
+                .inner_margin(egui::Margin::symmetric(12, 6))
+        };
+        egui::Panel::top("nocaps-title")
+            .frame(bar())
+            .show_separator_line(false)
+            .show_inside(ui, |ui| self.title_bar(ui));
+        egui::Panel::top("nocaps-profiles")
+            .frame(egui::Frame::new().inner_margin(egui::Margin::symmetric(12, 6)))
+            .show_separator_line(false)
+            .show_inside(ui, |ui| self.profile_bar(ui));
+        egui::Panel::bottom("nocaps-status")
+            .frame(bar())
+            .show_separator_line(false)
+            .show_inside(ui, |ui| self.status_bar(ui));
+
+        // Central grid: two columns hold all five groups at once — no scrolling.
+        egui::CentralPanel::default()
+            .frame(egui::Frame::new().inner_margin(egui::Margin::symmetric(10, 8)))
+            .show_inside(ui, |ui| match self.view {
+                View::Bindings => ui.columns(2, |columns| {
+                    self.category_block(&mut columns[0], Category::Modifiers);
+                    self.category_block(&mut columns[0], Category::Navigation);
+                    self.category_block(&mut columns[1], Category::Editing);
+                    self.category_block(&mut columns[1], Category::Volume);
+                    self.category_block(&mut columns[1], Category::Media);
+                }),
+                View::Keyboard => self.keyboard_view(ui),
+                View::Usage => self.usage_view(ui),
+                #[cfg(target_os = "linux")]
+                View::Inspector => self.inspector_view(ui),
+                #[cfg(target_os = "linux")]
+                View::Setup => self.setup_view(ui),
+                #[cfg(target_os = "linux")]
+                View::Diagnostics => self.diagnostics_view(ui),
+            });
+    }
+}
+
+fn configure_style(context: &egui::Context) {
+    let mut style = (*context.global_style()).clone();
+    style.spacing.item_spacing = egui::vec2(6.0, 4.0);
+    style.spacing.button_padding = egui::vec2(7.0, 3.0);
+    context.set_global_style(style);
+}
+
+fn input_key_from_egui(key: egui::Key) -> Option<InputKey> {
+    Some(match key {
+        egui::Key::A => InputKey::A,
+        egui::Key::B => InputKey::B,
+        egui::Key::C => InputKey::C,
+        egui::Key::D => InputKey::D,
+        egui::Key::E => InputKey::E,
+        egui::Key::F => InputKey::F,
+        egui::Key::G => InputKey::G,
+        egui::Key::H => InputKey::H,
+        egui::Key::I => InputKey::I,
+        egui::Key::J => InputKey::J,
+        egui::Key::K => InputKey::K,
+        egui::Key::L => InputKey::L,
+        egui::Key::M => InputKey::M,
+        egui::Key::N => InputKey::N,
+        egui::Key::O => InputKey::O,
+        egui::Key::P => InputKey::P,
+        egui::Key::Q => InputKey::Q,
+        egui::Key::R => InputKey::R,
+        egui::Key::S => InputKey::S,
+        egui::Key::T => InputKey::T,
+        egui::Key::U => InputKey::U,
+        egui::Key::V => InputKey::V,
+        egui::Key::W => InputKey::W,
+        egui::Key::X => InputKey::X,
+        egui::Key::Y => InputKey::Y,
+        egui::Key::Z => InputKey::Z,
+        egui::Key::Num0 => InputKey::Digit0,
+        egui::Key::Num1 => InputKey::Digit1,
+        egui::Key::Num2 => InputKey::Digit2,
+        egui::Key::Num3 => InputKey::Digit3,
+        egui::Key::Num4 => InputKey::Digit4,
+        egui::Key::Num5 => InputKey::Digit5,
+        egui::Key::Num6 => InputKey::Digit6,
+        egui::Key::Num7 => InputKey::Digit7,
+        egui::Key::Num8 => InputKey::Digit8,
+        egui::Key::Num9 => InputKey::Digit9,
+        egui::Key::Backtick => InputKey::Backquote,
+        egui::Key::Minus => InputKey::Minus,
+        egui::Key::Equals | egui::Key::Plus => InputKey::Equal,
+        egui::Key::OpenBracket | egui::Key::OpenCurlyBracket => InputKey::LeftBracket,
+        egui::Key::CloseBracket | egui::Key::CloseCurlyBracket => InputKey::RightBracket,
+        egui::Key::Backslash | egui::Key::Pipe => InputKey::Backslash,
+        egui::Key::Semicolon | egui::Key::Colon => InputKey::Semicolon,
+        egui::Key::Quote => InputKey::Quote,
+        egui::Key::Comma => InputKey::Comma,
+        egui::Key::Period => InputKey::Period,
+        egui::Key::Slash | egui::Key::Questionmark => InputKey::Slash,
+        egui::Key::Tab => InputKey::Tab,
+        egui::Key::Space => InputKey::Space,
+        egui::Key::Enter => InputKey::Enter,
+        egui::Key::Escape => InputKey::Escape,
+        egui::Key::Backspace => InputKey::Backspace,
+        egui::Key::Delete => InputKey::Delete,
+        egui::Key::Insert => InputKey::Insert,
+        egui::Key::Home => InputKey::Home,
+        egui::Key::End => InputKey::End,
+        egui::Key::PageUp => InputKey::PageUp,
+        egui::Key::PageDown => InputKey::PageDown,
+        egui::Key::ArrowUp => InputKey::ArrowUp,
+        egui::Key::ArrowDown => InputKey::ArrowDown,
+        egui::Key::ArrowLeft => InputKey::ArrowLeft,
+        egui::Key::ArrowRight => InputKey::ArrowRight,
+        egui::Key::F1 => InputKey::F1,
+        egui::Key::F2 => InputKey::F2,
+        egui::Key::F3 => InputKey::F3,
+        egui::Key::F4 => InputKey::F4,
+        egui::Key::F5 => InputKey::F5,
+        egui::Key::F6 => InputKey::F6,
+        egui::Key::F7 => InputKey::F7,
+        egui::Key::F8 => InputKey::F8,
+        egui::Key::F9 => InputKey::F9,
+        egui::Key::F10 => InputKey::F10,
+        egui::Key::F11 => InputKey::F11,
+        egui::Key::F12 => InputKey::F12,
+        egui::Key::F13 => InputKey::F13,
+        egui::Key::F14 => InputKey::F14,
+        egui::Key::F15 => InputKey::F15,
+        egui::Key::F16 => InputKey::F16,
+        egui::Key::F17 => InputKey::F17,
+        egui::Key::F18 => InputKey::F18,
+        egui::Key::F19 => InputKey::F19,
+        egui::Key::F20 => InputKey::F20,
+        egui::Key::F21 => InputKey::F21,
+        egui::Key::F22 => InputKey::F22,
+        egui::Key::F23 => InputKey::F23,
+        egui::Key::F24 => InputKey::F24,
+        _ => return None,
+    })
+}
+
+fn window_icon() -> egui::IconData {
+    egui::IconData {
+        rgba: ICON_RGBA.to_vec(),
+        width: ICON_SIZE,
+        height: ICON_SIZE,
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn create_tray(config: &Config, locale: &'static Locale) -> Result<Tray> {
+    let appindicator_available = ["libayatana-appindicator3.so.1", "libappindicator3.so.1"]
+        .iter()
+        .any(|name| unsafe { libloading::Library::new(name).is_ok() });
+    if !appindicator_available {
+        return Err(anyhow::anyhow!(
+            "install the Ayatana AppIndicator 3 runtime library"
+        ));
+    }
+    std::panic::catch_unwind(|| Tray::new(config, locale))
+        .map_err(|_| anyhow::anyhow!("create Linux tray icon"))?
+}
+
+#[cfg(not(target_os = "linux"))]
+fn create_tray(config: &Config, locale: &'static Locale) -> Result<Tray> {
+    Tray::new(config, locale)
+}
+
+fn enabled_menu_text(locale: &Locale, enabled: bool) -> &str {
+    locale.text(if enabled {
+        "tray.enabled"
+    } else {
+        "tray.disabled"
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn pump_native_tray_events() {
+    while gtk::events_pending() {
+        gtk::main_iteration_do(false);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pump_native_tray_events() {}
@@ -0,0 +1,155 @@
+use crate::config::Theme;
+use eframe::egui;
+
+/// The colours of one theme, by the role they play in the window.
+pub(super) struct Palette {
+    // Light themes start from egui's light visuals instead of its dark ones.
+    light: bool,
+    pub(super) screen: egui::Color32,
+    pub(super) panel: egui::Color32,
+    pub(super) hover: egui::Color32,
+    pub(super) keycap_hot: egui::Color32,
+    // Unbound keys on the keyboard diagram, a shade off the panels.
+    pub(super) free_key: egui::Color32,
+    pub(super) text: egui::Color32,
+    pub(super) dim: egui::Color32,
+    pub(super) faint: egui::Color32,
+    // What is live: the held layer, the key being captured, hovered widgets.
+    pub(super) accent: egui::Color32,
+    pub(super) alarm: egui::Color32,
+    // Opacity of the scanlines drawn over the screen; zero leaves them out.
+    scanlines: u8,
+}
+
+// Green-phosphor CRT: a dark screen, glowing text, amber for "live".
+const CRT_GREEN: Palette = Palette {
+    light: false,
+    screen: egui::Color32::from_rgb(6, 15, 9),
+    panel: egui::Color32::from_rgb(12, 26, 16),
+    hover: egui::Color32::from_rgb(22, 46, 30),
+    keycap_hot: egui::Color32::from_rgb(48, 40, 14),
+    free_key: egui::Color32::from_rgb(20, 44, 28),
+    text: egui::Color32::from_rgb(128, 240, 152),
+    dim: egui::Color32::from_rgb(78, 160, 104),
+    faint: egui::Color32::from_rgb(42, 92, 60),
+    accent: egui::Color32::from_rgb(255, 196, 92),
+    alarm: egui::Color32::from_rgb(255, 104, 92),
+    scanlines: 38,
+};
+
+// Amber monochrome, with pale yellow for "live".
+const AMBER: Palette = Palette {
+    light: false,
+    screen: egui::Color32::from_rgb(15, 10, 4),
+    panel: egui::Color32::from_rgb(28, 19, 8),
+    hover: egui::Color32::from_rgb(48, 32, 12),
+    keycap_hot: egui::Color32::from_rgb(64, 34, 10),
+    free_key: egui::Color32::from_rgb(44, 30, 12),
+    text: egui::Color32::from_rgb(255, 186, 84),
+    dim: egui::Color32::from_rgb(196, 132, 56),
+    faint: egui::Color32::from_rgb(104, 70, 30),
+    accent: egui::Color32::from_rgb(255, 236, 170),
+    alarm: egui::Color32::from_rgb(255, 96, 80),
+    scanlines: 38,
+};
+
+// Dark text on paper for bright rooms; no scanlines.
+const LIGHT: Palette = Palette {
+    light: true,
+    screen: egui::Color32::from_rgb(244, 242, 236),
+    panel: egui::Color32::from_rgb(230, 227, 218),
+    hover: egui::Color32::from_rgb(216, 228, 218),
+    keycap_hot: egui::Color32::from_rgb(255, 224, 160),
+    free_key: egui::Color32::from_rgb(212, 234, 216),
+    text: egui::Color32::from_rgb(24, 32, 28),
+    dim: egui::Color32::from_rgb(78, 90, 84),
+    faint: egui::Color32::from_rgb(128, 138, 132),
+    accent: egui::Color32::from_rgb(168, 84, 0),
+    alarm: egui::Color32::from_rgb(190, 32, 24),
+    scanlines: 0,
+};
+
+// White on black with pure yellow for "live", and nothing drawn over it.
+const HIGH_CONTRAST: Palette = Palette {
+    light: false,
+    screen: egui::Color32::BLACK,
+    panel: egui::Color32::from_rgb(24, 24, 24),
+    hover: egui::Color32::from_rgb(48, 48, 48),
+    keycap_hot: egui::Color32::from_rgb(0, 0, 150),
+    free_key: egui::Color32::from_rgb(0, 64, 0),
+    text: egui::Color32::WHITE,
+    dim: egui::Color32::from_rgb(230, 230, 230),
+    faint: egui::Color32::from_rgb(180, 180, 180),
+    accent: egui::Color32::YELLOW,
+    alarm: egui::Color32::from_rgb(255, 90, 90),
+    scanlines: 0,
+};
+
+pub(super) fn palette(theme: Theme) -> &'static Palette {
+    match theme {
+        Theme::CrtGreen => &CRT_GREEN,
+        Theme::Amber => &AMBER,
+        Theme::Light => &LIGHT,
+        Theme::HighContrast => &HIGH_CONTRAST,
+    }
+}
+
+/// Faint horizontal lines across the whole surface for a CRT feel; panels paint
+/// over them, so they only show through the screen background and the gutters.
+pub(super) fn draw_scanlines(painter: &egui::Painter, palette: &Palette, rect: egui::Rect) {
+    if palette.scanlines == 0 {
+        return;
+    }
+    let line = egui::Stroke::new(
+        1.0,
+        egui::Color32::from_rgba_unmultiplied(0, 0, 0, palette.scanlines),
+    );
+    let mut y = rect.top();
+    while y < rect.bottom() {
+        painter.hline(rect.x_range(), y, line);
+        y += 3.0;
+    }
+}
+
+/// Colours the window after `palette`, at startup and whenever the theme
+/// changes.
+pub(super) fn apply_palette(context: &egui::Context, palette: &Palette) {
+    let mut visuals = if palette.light {
+        egui::Visuals::light()
+    } else {
+        egui::Visuals::dark()
+    };
+    visuals.override_text_color = Some(palette.text);
+    visuals.window_fill = palette.screen;
+    visuals.panel_fill = palette.screen;
+    visuals.extreme_bg_color = palette.screen;
+    visuals.faint_bg_color = palette.panel;
+    visuals.warn_fg_color = palette.accent;
+    visuals.error_fg_color = palette.alarm;
+    visuals.selection.bg_fill = palette.faint;
+    visuals.selection.stroke = egui::Stroke::new(1.0, palette.screen);
+
+    // Sharp corners everywhere — no rounded edges on a CRT.
+    let sharp = egui::CornerRadius::same(0);
+    let widgets = &mut visuals.widgets;
+    widgets.noninteractive.corner_radius = sharp;
+    widgets.noninteractive.bg_stroke = egui::Stroke::new(1.0, palette.faint);
+    widgets.noninteractive.fg_stroke = egui::Stroke::new(1.0, palette.text);
+    widgets.inactive.corner_radius = sharp;
+    widgets.inactive.bg_fill = palette.panel;
+    widgets.inactive.weak_bg_fill = palette.panel;
+    widgets.inactive.bg_stroke = egui::Stroke::new(1.0, palette.faint);
+    widgets.inactive.fg_stroke = egui::Stroke::new(1.0, palette.text);
+    widgets.hovered.corner_radius = sharp;
+    widgets.hovered.bg_fill = palette.hover;
+    widgets.hovered.weak_bg_fill = palette.hover;
+    widgets.hovered.bg_stroke = egui::Stroke::new(1.0, palette.accent);
+    widgets.hovered.fg_stroke = egui::Stroke::new(1.0, palette.accent);
+    widgets.active.corner_radius = sharp;
+    widgets.active.bg_fill = palette.faint;
+    widgets.active.weak_bg_fill = palette.faint;
+    widgets.active.bg_stroke = egui::Stroke::new(1.0, palette.accent);
+    widgets.active.fg_stroke = egui::Stroke::new(1.0, palette.screen);
+    widgets.open.corner_radius = sharp;
+    context.set_visuals(visuals);
+}
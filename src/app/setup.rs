@@ -0,0 +1,176 @@
+use super::{NocapsApp, Status};
+use crate::platform::setup::{self, Item, State};
+use eframe::egui;
+
+pub(super) use crate::platform::setup::{check, Check};
+
+impl NocapsApp {
+    /// The permissions the Linux backend needs, with a rule and a pkexec
+    /// helper to grant the missing ones.
+    pub(super) fn setup_view(&mut self, ui: &mut egui::Ui) {
+        let palette = self.palette;
+        let locale = self.locale;
+        ui.horizontal(|ui| {
+            ui.label(
+                egui::RichText::new(format!("[ {} ]", locale.text("setup.heading")))
+                    .color(palette.accent)
+                    .size(12.0)
+                    .strong(),
+            );
+            ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                if ui
+                    .add(egui::Button::new(
+                        egui::RichText::new(locale.text("setup.check_again"))
+                            .color(palette.dim)
+                            .size(12.0),
+                    ))
+                    .clicked()
+                {
+                    self.permissions = setup::check();
+                }
+            });
+        });
+        ui.label(
+            egui::RichText::new(locale.text("setup.intro"))
+                .color(palette.dim)
+                .size(11.0),
+        );
+        ui.add_space(6.0);
+
+        let user = setup::user_name().unwrap_or_default();
+        let executable = std::env::current_exe()
+            .map(|path| path.display().to_string())
+            .unwrap_or_else(|_| "nocaps".to_owned());
+        egui::Grid::new("nocaps-setup")
+            .num_columns(3)
+            .spacing(egui::vec2(10.0, 6.0))
+            .show(ui, |ui| {
+                for check in &self.permissions {
+                    let (state, color) = match check.state {
+                        State::Ok => ("setup.ok", palette.accent),
+                        State::Missing => ("setup.missing", palette.alarm),
+                        State::LogInAgain => ("setup.log_in_again", palette.alarm),
+                        State::Optional => ("setup.optional", palette.dim),
+                    };
+                    let (item, fix) = match check.item {
+                        Item::InputGroup => ("setup.input_group", "setup.input_group_fix"),
+                        Item::Keyboards => ("setup.keyboards", "setup.keyboards_fix"),
+                        Item::UinputModule => ("setup.uinput_module", "setup.uinput_module_fix"),
+                        Item::Uinput => ("setup.uinput", "setup.uinput_fix"),
+                        Item::Realtime => ("setup.realtime", "setup.realtime_fix"),
+                    };
+                    let values = [("user", user.as_str()), ("path", executable.as_str())];
+                    ui.label(
+                        egui::RichText::new(format!("[{}]", locale.text(state)))
+                            .color(color)
+                            .size(12.0)
+                            .strong(),
+                    );
+                    ui.vertical(|ui| {
+                        ui.label(egui::RichText::new(locale.fill(item, &values)).size(12.0));
+                        if check.state != State::Ok {
+                            ui.label(
+                                egui::RichText::new(locale.fill(fix, &values))
+                                    .color(palette.dim)
+                                    .size(11.0),
+                            );
+                        }
+                    });
+                    ui.label(
+                        egui::RichText::new(&check.detail)
+                            .color(palette.faint)
+                            .size(11.0),
+                    );
+                    ui.end_row();
+                }
+            });
+
+        ui.add_space(10.0);
+        ui.label(
+            egui::RichText::new(format!("[ {} ]", locale.text("setup.rule_heading")))
+                .color(palette.dim)
+                .size(12.0)
+                .strong(),
+        );
+        ui.label(
+            egui::RichText::new(locale.fill("setup.rule_hint", &[("path", setup::UDEV_RULE_PATH)]))
+                .color(palette.dim)
+                .size(11.0),
+        );
+        let mut rule = setup::UDEV_RULE;
+        ui.add(
+            egui::TextEdit::multiline(&mut rule)
+                .code_editor()
+                .desired_rows(3)
+                .desired_width(f32::INFINITY),
+        );
+        ui.horizontal(|ui| {
+            if ui
+                .add(egui::Button::new(
+                    egui::RichText::new(locale.text("setup.copy"))
+                        .color(palette.dim)
+                        .size(12.0),
+                ))
+                .clicked()
+            {
+                ui.ctx().copy_text(setup::UDEV_RULE.to_owned());
+                self.status = Some(Status {
+                    is_error: false,
+                    message: locale.text("status.rule_copied").to_owned(),
+                });
+            }
+            let installing = self.installing.is_some();
+            let text = locale.text(if installing {
+                "setup.installing"
+            } else {
+                "setup.install"
+            });
+            if ui
+                .add_enabled(
+                    !installing,
+                    egui::Button::new(egui::RichText::new(text).color(palette.text).size(12.0)),
+                )
+                .on_hover_text(locale.fill("setup.install_hover", &[("user", &user)]))
+                .clicked()
+            {
+                self.install_permissions(ui.ctx());
+            }
+        });
+    }
+
+    /// Runs the pkexec helper on its own thread, since it waits for the
+    /// password dialog.
+    fn install_permissions(&mut self, context: &egui::Context) {
+        let (done, installing) = std::sync::mpsc::channel();
+        let waker = context.clone();
+        std::thread::spawn(move || {
+            let result = setup::install_permissions().map_err(|error| format!("{error:#}"));
+            let _ = done.send(result);
+            waker.request_repaint();
+        });
+        self.installing = Some(installing);
+    }
+
+    pub(super) fn finish_installing(&mut self) {
+        let Some(result) = self
+            .installing
+            .as_ref()
+            .and_then(|installing| installing.try_recv().ok())
+        else {
+            return;
+        };
+        let locale = self.locale;
+        self.installing = None;
+        self.permissions = setup::check();
+        self.status = Some(match result {
+            Ok(()) => Status {
+                is_error: false,
+                message: locale.text("status.setup_installed").to_owned(),
+            },
+            Err(error) => Status {
+                is_error: true,
+                message: locale.fill("status.setup_failed", &[("error", &error)]),
+            },
+        });
+    }
+}
@@ -0,0 +1,429 @@
+use crate::config::{Action, Config, ConfigStore, InputKey, ToggleHotkey};
+use crate::migration;
+use anyhow::{anyhow, Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+const USAGE: &str = "\
+usage: nocaps [command]
+
+Without a command, nocaps starts remapping and puts its icon in the tray.
+
+commands:
+  --headless            remap without a window or tray icon until stopped
+  list                  show the actions and keys of the active profile
+  bind <action> <key>   make Caps Lock + key invoke the action
+  unbind <action>       clear the action's binding
+  enable                turn remapping on
+  disable               turn remapping off
+  toggle-key <key> [<ms>]
+                        hold Caps Lock + key for ms (default 1000) to turn
+                        remapping on or off
+  toggle-key off        turn the toggle gesture off
+  validate <file>       check a configuration file without applying it
+  profile list          show the profiles, marking the active one
+  profile use <name>    make the named profile active
+  profile new <name>    add an empty profile and make it active
+  profile copy <name>   copy the active profile under a new name
+  profile rename <name> rename the active profile
+  profile delete        delete the active profile
+  service install [--headless]
+                        start nocaps when you log in (Linux)
+  service uninstall     stop starting nocaps when you log in
+  service status        show how nocaps is started at login
+  help                  show this message
+
+Action and key names are the ones used in config.json, e.g. `nocaps bind arrow_up i`.
+A running nocaps applies changes as soon as they are saved.";
+
+/// Exit status for a command line that could not be understood.
+const USAGE_ERROR: u8 = 2;
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Run,
+    Headless,
+    List,
+    Bind(Action, InputKey),
+    Unbind(Action),
+    SetEnabled(bool),
+    SetToggle(Option<ToggleHotkey>),
+    Validate(PathBuf),
+    Profiles,
+    Profile(ProfileCommand),
+    Service(ServiceCommand),
+    Help,
+    Version,
+}
+
+/// `nocaps profile …` edits; all but `use` act on the active profile.
+#[derive(Debug, PartialEq)]
+pub enum ProfileCommand {
+    Use(String),
+    New(String),
+    Copy(String),
+    Rename(String),
+    Delete,
+}
+
+/// `nocaps service …`: manages starting nocaps with the desktop session.
+#[derive(Debug, PartialEq)]
+pub enum ServiceCommand {
+    Install { headless: bool },
+    Uninstall,
+    Status,
+}
+
+impl Command {
+    pub fn parse<I>(args: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let args: Vec<String> = args.into_iter().collect();
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        Ok(match args.as_slice() {
+            [] => Self::Run,
+            ["--headless"] => Self::Headless,
+            ["list"] => Self::List,
+            ["bind", action, key] => {
+                Self::Bind(parse_name(action, "action")?, parse_name(key, "key")?)
+            }
+            ["unbind", action] => Self::Unbind(parse_name(action, "action")?),
+            ["enable"] => Self::SetEnabled(true),
+            ["disable"] => Self::SetEnabled(false),
+            ["toggle-key", "off"] => Self::SetToggle(None),
+            ["toggle-key", key] => Self::SetToggle(Some(ToggleHotkey {
+                key: parse_name(key, "key")?,
+                hold_ms: 1000,
+            })),
+            ["toggle-key", key, hold_ms] => Self::SetToggle(Some(ToggleHotkey {
+                key: parse_name(key, "key")?,
+                hold_ms: hold_ms
+                    .parse()
+                    .map_err(|_| anyhow!("`{hold_ms}` is not a number of milliseconds"))?,
+            })),
+            ["validate", path] => Self::Validate(PathBuf::from(path)),
+            ["profile", "list"] => Self::Profiles,
+            ["profile", "use", name] => Self::Profile(ProfileCommand::Use(name.to_string())),
+            ["profile", "new", name] => Self::Profile(ProfileCommand::New(name.to_string())),
+            ["profile", "copy", name] => Self::Profile(ProfileCommand::Copy(name.to_string())),
+            ["profile", "rename", name] => Self::Profile(ProfileCommand::Rename(name.to_string())),
+            ["profile", "delete"] => Self::Profile(ProfileCommand::Delete),
+            ["profile", ..] => {
+                return Err(anyhow!(
+                "expected `profile list`, `profile use|new|copy|rename <name>` or `profile delete`"
+            ))
+            }
+            ["service", "install"] => Self::Service(ServiceCommand::Install { headless: false }),
+            ["service", "install", "--headless"] => {
+                Self::Service(ServiceCommand::Install { headless: true })
+            }
+            ["service", "uninstall"] => Self::Service(ServiceCommand::Uninstall),
+            ["service", "status"] => Self::Service(ServiceCommand::Status),
+            ["service", ..] => {
+                return Err(anyhow!(
+                "expected `service install [--headless]`, `service uninstall` or `service status`"
+            ))
+            }
+            ["help" | "-h" | "--help"] => Self::Help,
+            ["-V" | "--version"] => Self::Version,
+            [command, ..] => match *command {
+                "list" | "bind" | "unbind" | "enable" | "disable" | "toggle-key" | "validate" => {
+                    return Err(anyhow!("wrong number of arguments for `{command}`"))
+                }
+                _ => return Err(anyhow!("unknown command `{command}`")),
+            },
+        })
+    }
+}
+
+/// Reports a command line that could not be parsed and returns its exit status.
+pub fn usage_error(error: &anyhow::Error) -> ExitCode {
+    attach_console();
+    eprintln!("nocaps: {error}\n\n{USAGE}");
+    ExitCode::from(USAGE_ERROR)
+}
+
+/// Runs a command that does not need the remapper or a window.
+pub fn execute(command: Command) -> ExitCode {
+    attach_console();
+    match run(command) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("nocaps: {error:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(command: Command) -> Result<()> {
+    match command {
+        Command::Run | Command::Headless => {
+            Err(anyhow!("the remapper is not a command-line command"))
+        }
+        Command::Help => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        Command::Version => {
+            println!("nocaps {}", env!("CARGO_PKG_VERSION"));
+            Ok(())
+        }
+        Command::Validate(path) => {
+            let bytes = fs::read(&path).with_context(|| format!("read {}", path.display()))?;
+            let upgrade =
+                migration::upgrade(&bytes).with_context(|| format!("parse {}", path.display()))?;
+            upgrade.config.validate()?;
+            match upgrade.upgraded_from {
+                Some(version) => println!(
+                    "{} is valid (version {version}; nocaps will upgrade it on load)",
+                    path.display()
+                ),
+                None => println!("{} is valid", path.display()),
+            }
+            Ok(())
+        }
+        Command::List => {
+            let store = ConfigStore::discover()?;
+            let config = store.load_or_create()?;
+            println!(
+                "remapping {}, profile {} — {}",
+                if config.enabled {
+                    "enabled"
+                } else {
+                    "disabled"
+                },
+                config.profile,
+                store.path().display()
+            );
+            match config.toggle {
+                Some(toggle) => println!(
+                    "toggle: hold Caps Lock + {} for {} ms",
+                    name(toggle.key),
+                    toggle.hold_ms
+                ),
+                None => println!("toggle: off"),
+            }
+            for action in Action::ALL.iter().copied() {
+                let key = config
+                    .key_for(action)
+                    .map(name)
+                    .unwrap_or_else(|| "-".to_owned());
+                println!("{:<20}{key}", name(action));
+            }
+            Ok(())
+        }
+        Command::Bind(action, key) => edit(|config| {
+            let previous = config
+                .bindings()
+                .iter()
+                .find(|(bound, current)| **current == key && **bound != action)
+                .map(|(bound, _)| *bound);
+            config.bind(action, key);
+            Ok(match previous {
+                Some(previous) => format!(
+                    "{} is now Caps Lock + {} (removed from {})",
+                    name(action),
+                    name(key),
+                    name(previous)
+                ),
+                None => format!("{} is now Caps Lock + {}", name(action), name(key)),
+            })
+        }),
+        Command::Unbind(action) => edit(|config| {
+            if config.key_for(action).is_none() {
+                return Ok(format!("{} was not bound", name(action)));
+            }
+            config.unbind(action);
+            Ok(format!("{} cleared", name(action)))
+        }),
+        Command::SetEnabled(enabled) => edit(|config| {
+            config.enabled = enabled;
+            Ok(if enabled {
+                "remapping enabled".to_owned()
+            } else {
+                "remapping disabled".to_owned()
+            })
+        }),
+        Command::SetToggle(toggle) => edit(|config| {
+            config.toggle = toggle;
+            Ok(match toggle {
+                Some(toggle) => format!(
+                    "hold Caps Lock + {} for {} ms to turn remapping on or off",
+                    name(toggle.key),
+                    toggle.hold_ms
+                ),
+                None => "toggle gesture off".to_owned(),
+            })
+        }),
+        Command::Profiles => {
+            let config = ConfigStore::discover()?.load_or_create()?;
+            for name in config.profiles.keys() {
+                let marker = if *name == config.profile { '*' } else { ' ' };
+                println!("{marker} {name}");
+            }
+            Ok(())
+        }
+        Command::Profile(command) => edit(|config| profile(config, command)),
+        Command::Service(command) => service(command),
+    }
+}
+
+fn profile(config: &mut Config, command: ProfileCommand) -> Result<String> {
+    let current = config.profile.clone();
+    Ok(match command {
+        ProfileCommand::Use(name) => {
+            config.switch_profile(&name)?;
+            format!("profile {name} is now active")
+        }
+        ProfileCommand::New(name) => {
+            config.create_profile(&name)?;
+            format!("created empty profile {}", config.profile)
+        }
+        ProfileCommand::Copy(name) => {
+            config.duplicate_profile(&name)?;
+            format!("copied {current} to {}", config.profile)
+        }
+        ProfileCommand::Rename(name) => {
+            config.rename_profile(&name)?;
+            format!("renamed {current} to {}", config.profile)
+        }
+        ProfileCommand::Delete => {
+            config.delete_profile()?;
+            format!("deleted {current}; {} is now active", config.profile)
+        }
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn service(command: ServiceCommand) -> Result<()> {
+    let service = crate::service::Service::discover()?;
+    match command {
+        ServiceCommand::Install { headless } => {
+            // A build without the window can only run headless.
+            let installation = service.install(headless || !cfg!(feature = "gui"))?;
+            println!("installed {installation}; nocaps will start when you log in");
+        }
+        ServiceCommand::Uninstall => {
+            let removed = service.uninstall()?;
+            if removed.is_empty() {
+                println!("nocaps was not set to start at login");
+            }
+            for path in removed {
+                println!("removed {}", path.display());
+            }
+        }
+        ServiceCommand::Status => match service.installed() {
+            None => println!("nocaps is not set to start at login"),
+            Some(installation) => {
+                println!("{installation}");
+                if let Some(command) = installation.command() {
+                    println!("  runs:  {command}");
+                }
+                if let Some(state) = installation.state() {
+                    println!("  state: {state}");
+                }
+            }
+        },
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn service(_command: ServiceCommand) -> Result<()> {
+    Err(anyhow!(
+        "`nocaps service` manages Linux autostart; use your system's login items instead"
+    ))
+}
+
+/// Loads the user configuration, applies `change` and saves the result. Running
+/// instances pick the saved file up through their configuration watcher.
+fn edit(change: impl FnOnce(&mut Config) -> Result<String>) -> Result<()> {
+    let store = ConfigStore::discover()?;
+    let mut config = store.load_or_create()?;
+    let message = change(&mut config)?;
+    store.save(&config)?;
+    println!("{message}");
+    Ok(())
+}
+
+fn parse_name<T: DeserializeOwned>(name: &str, kind: &str) -> Result<T> {
+    serde_json::from_value(serde_json::Value::String(name.to_owned()))
+        .map_err(|_| anyhow!("unknown {kind} `{name}`; run `nocaps list` to see the names in use"))
+}
+
+/// The snake_case name used for `value` in config.json.
+fn name<T: Serialize>(value: T) -> String {
+    match serde_json::to_value(value) {
+        Ok(serde_json::Value::String(name)) => name,
+        _ => String::new(),
+    }
+}
+
+/// The release build uses the GUI subsystem, so Windows starts it without a
+/// console. Borrow the console of the shell that launched the command.
+#[cfg(target_os = "windows")]
+fn attach_console() {
+    use windows::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+
+    unsafe {
+        let _ = AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(target_os = "windows"))]
+fn attach_console() {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Command> {
+        Command::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn no_arguments_starts_the_remapper() {
+        assert_eq!(parse(&[]).unwrap(), Command::Run);
+    }
+
+    #[test]
+    fn commands_use_the_names_from_config_json() {
+        assert_eq!(
+            parse(&["bind", "arrow_up", "i"]).unwrap(),
+            Command::Bind(Action::ArrowUp, InputKey::I)
+        );
+        assert_eq!(
+            parse(&["unbind", "volume_mute"]).unwrap(),
+            Command::Unbind(Action::VolumeMute)
+        );
+        assert_eq!(parse(&["disable"]).unwrap(), Command::SetEnabled(false));
+        assert_eq!(
+            parse(&["toggle-key", "f12", "1500"]).unwrap(),
+            Command::SetToggle(Some(ToggleHotkey {
+                key: InputKey::F12,
+                hold_ms: 1500
+            }))
+        );
+        assert_eq!(
+            parse(&["toggle-key", "off"]).unwrap(),
+            Command::SetToggle(None)
+        );
+        assert_eq!(
+            parse(&["service", "install", "--headless"]).unwrap(),
+            Command::Service(ServiceCommand::Install { headless: true })
+        );
+    }
+
+    #[test]
+    fn unknown_names_and_arity_are_usage_errors() {
+        assert!(parse(&["bind", "arrow_upp", "i"]).is_err());
+        assert!(parse(&["bind", "arrow_up"]).is_err());
+        assert!(parse(&["frobnicate"]).is_err());
+        assert!(parse(&["service", "start"]).is_err());
+        assert!(parse(&["profile", "use"]).is_err());
+    }
+}
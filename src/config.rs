@@ -1,13 +1,22 @@
+#[cfg(target_os = "linux")]
+use crate::diagnostics::Diagnostics;
+#[cfg(target_os = "linux")]
+use crate::inspector::Inspector;
+use crate::migration;
+use crate::usage::Usage;
 use anyhow::{anyhow, Context, Result};
 use arc_swap::ArcSwap;
 use directories::BaseDirs;
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, HashSet};
+use std::fmt;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 
-pub const CONFIG_VERSION: u32 = 1;
+pub const CONFIG_VERSION: u32 = 6;
 pub const DEFAULT_CONFIG_JSON: &str = include_str!("../config/default.json");
 
 /// Physical keys that can activate an action while Caps Lock is held.
@@ -107,6 +116,95 @@ pub enum InputKey {
 impl InputKey {
     pub const COUNT: usize = Self::F24 as usize + 1;
 
+    pub const ALL: [Self; Self::COUNT] = [
+        Self::A,
+        Self::B,
+        Self::C,
+        Self::D,
+        Self::E,
+        Self::F,
+        Self::G,
+        Self::H,
+        Self::I,
+        Self::J,
+        Self::K,
+        Self::L,
+        Self::M,
+        Self::N,
+        Self::O,
+        Self::P,
+        Self::Q,
+        Self::R,
+        Self::S,
+        Self::T,
+        Self::U,
+        Self::V,
+        Self::W,
+        Self::X,
+        Self::Y,
+        Self::Z,
+        Self::Digit0,
+        Self::Digit1,
+        Self::Digit2,
+        Self::Digit3,
+        Self::Digit4,
+        Self::Digit5,
+        Self::Digit6,
+        Self::Digit7,
+        Self::Digit8,
+        Self::Digit9,
+        Self::Backquote,
+        Self::Minus,
+        Self::Equal,
+        Self::LeftBracket,
+        Self::RightBracket,
+        Self::Backslash,
+        Self::Semicolon,
+        Self::Quote,
+        Self::Comma,
+        Self::Period,
+        Self::Slash,
+        Self::Tab,
+        Self::Space,
+        Self::Enter,
+        Self::Escape,
+        Self::Backspace,
+        Self::Delete,
+        Self::Insert,
+        Self::Home,
+        Self::End,
+        Self::PageUp,
+        Self::PageDown,
+        Self::ArrowUp,
+        Self::ArrowDown,
+        Self::ArrowLeft,
+        Self::ArrowRight,
+        Self::F1,
+        Self::F2,
+        Self::F3,
+        Self::F4,
+        Self::F5,
+        Self::F6,
+        Self::F7,
+        Self::F8,
+        Self::F9,
+        Self::F10,
+        Self::F11,
+        Self::F12,
+        Self::F13,
+        Self::F14,
+        Self::F15,
+        Self::F16,
+        Self::F17,
+        Self::F18,
+        Self::F19,
+        Self::F20,
+        Self::F21,
+        Self::F22,
+        Self::F23,
+        Self::F24,
+    ];
+
     pub const fn index(self) -> usize {
         self as usize
     }
@@ -263,6 +361,12 @@ impl Action {
         Self::MediaNext,
     ];
 
+    pub const COUNT: usize = Self::ALL.len();
+
+    pub const fn index(self) -> usize {
+        self as usize
+    }
+
     pub fn label(self) -> &'static str {
         match self {
             Self::LeftControl => "Control",
@@ -292,9 +396,26 @@ impl Action {
         }
     }
 
-    pub fn category(self) -> &'static str {
+    #[cfg(target_os = "macos")]
+    pub const fn held_code(self) -> u8 {
+        self as u8 + 1
+    }
+
+    #[cfg(target_os = "macos")]
+    pub fn from_held_code(code: u8) -> Option<Self> {
+        code.checked_sub(1)
+            .and_then(|index| Self::ALL.get(index as usize))
+            .copied()
+    }
+}
+
+#[cfg(feature = "gui")]
+impl Action {
+    pub fn category(self) -> Category {
         match self {
-            Self::LeftControl | Self::LeftShift | Self::LeftAlt | Self::LeftMeta => "Modifiers",
+            Self::LeftControl | Self::LeftShift | Self::LeftAlt | Self::LeftMeta => {
+                Category::Modifiers
+            }
             Self::ArrowUp
             | Self::ArrowDown
             | Self::ArrowLeft
@@ -302,38 +423,163 @@ impl Action {
             | Self::Home
             | Self::End
             | Self::PageUp
-            | Self::PageDown => "Navigation",
+            | Self::PageDown => Category::Navigation,
             Self::Backspace
             | Self::Delete
             | Self::Enter
             | Self::Escape
             | Self::Tab
-            | Self::Space => "Editing",
-            Self::VolumeUp | Self::VolumeDown | Self::VolumeMute => "Volume",
-            Self::MediaPrevious | Self::MediaPlayPause | Self::MediaNext => "Media",
+            | Self::Space => Category::Editing,
+            Self::VolumeUp | Self::VolumeDown | Self::VolumeMute => Category::Volume,
+            Self::MediaPrevious | Self::MediaPlayPause | Self::MediaNext => Category::Media,
         }
     }
+}
 
-    #[cfg(target_os = "macos")]
-    pub const fn held_code(self) -> u8 {
-        self as u8 + 1
+/// How the window groups actions. The name shown for each comes from the
+/// window's language; this is only its identity.
+#[cfg(feature = "gui")]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    Modifiers,
+    Navigation,
+    Editing,
+    Volume,
+    Media,
+}
+
+#[cfg(feature = "gui")]
+impl Category {
+    pub const ALL: &'static [Self] = &[
+        Self::Modifiers,
+        Self::Navigation,
+        Self::Editing,
+        Self::Volume,
+        Self::Media,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Modifiers => "Modifiers",
+            Self::Navigation => "Navigation",
+            Self::Editing => "Editing",
+            Self::Volume => "Volume",
+            Self::Media => "Media",
+        }
     }
+}
 
-    #[cfg(target_os = "macos")]
-    pub fn from_held_code(code: u8) -> Option<Self> {
-        code.checked_sub(1)
-            .and_then(|index| Self::ALL.get(index as usize))
-            .copied()
+/// Which key invokes each action while Caps Lock is held.
+pub type Bindings = BTreeMap<Action, InputKey>;
+
+const NO_BINDINGS: &Bindings = &BTreeMap::new();
+pub const PROFILE_NAME_LIMIT: usize = 40;
+const TOGGLE_HOLD_MS: std::ops::RangeInclusive<u64> = 300..=5000;
+
+/// Why a profile could not be switched to, created, copied, renamed or
+/// deleted. The window words each case in its own language; everything else
+/// shows the English text.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProfileError {
+    Missing(String),
+    LastProfile,
+    Unnamed,
+    TooLong,
+    Taken(String),
+}
+
+impl fmt::Display for ProfileError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing(name) => write!(formatter, "there is no profile named `{name}`"),
+            Self::LastProfile => formatter.write_str("the last profile cannot be deleted"),
+            Self::Unnamed => formatter.write_str("a profile needs a name"),
+            Self::TooLong => write!(
+                formatter,
+                "profile names are limited to {PROFILE_NAME_LIMIT} characters"
+            ),
+            Self::Taken(name) => write!(formatter, "a profile named `{name}` already exists"),
+        }
+    }
+}
+
+impl std::error::Error for ProfileError {}
+
+/// Caps Lock plus `key`, both held for `hold_ms` milliseconds, turns remapping
+/// on or off. The key is reserved for the gesture, so no profile may bind it.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct ToggleHotkey {
+    pub key: InputKey,
+    pub hold_ms: u64,
+}
+
+impl ToggleHotkey {
+    pub fn hold(&self) -> Duration {
+        Duration::from_millis(self.hold_ms)
     }
 }
 
-/// The on-disk format is intentionally direct: each action names the physical key
-/// that should invoke it while Caps Lock is held.
+/// The colours of the configuration window.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Theme {
+    #[default]
+    CrtGreen,
+    Amber,
+    Light,
+    HighContrast,
+}
+
+impl Theme {
+    #[cfg(feature = "gui")]
+    pub const ALL: &'static [Self] =
+        &[Self::CrtGreen, Self::Amber, Self::Light, Self::HighContrast];
+}
+
+/// The language of the configuration window and tray menu. `System` follows
+/// the locale environment variables.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub enum Language {
+    #[default]
+    #[serde(rename = "system")]
+    System,
+    #[serde(rename = "en")]
+    English,
+    #[serde(rename = "de")]
+    German,
+    #[serde(rename = "ja")]
+    Japanese,
+    #[serde(rename = "zh-Hans")]
+    SimplifiedChinese,
+}
+
+impl Language {
+    #[cfg(feature = "gui")]
+    pub const ALL: &'static [Self] = &[
+        Self::System,
+        Self::English,
+        Self::German,
+        Self::Japanese,
+        Self::SimplifiedChinese,
+    ];
+}
+
+/// The on-disk format is intentionally direct: each named profile maps actions
+/// to the physical key that should invoke them while Caps Lock is held, and
+/// `profile` names the one in effect.
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct Config {
     pub version: u32,
     pub enabled: bool,
-    pub bindings: BTreeMap<Action, InputKey>,
+    pub toggle: Option<ToggleHotkey>,
+    /// Opt-in: count how often each action is used, for the usage view.
+    pub statistics: bool,
+    /// Read only by the configuration window, like `language`.
+    pub theme: Theme,
+    pub language: Language,
+    pub profile: String,
+    pub profiles: BTreeMap<String, Bindings>,
 }
 
 impl Default for Config {
@@ -352,34 +598,137 @@ impl Config {
                 CONFIG_VERSION
             ));
         }
-        let mut keys = HashSet::new();
-        for key in self.bindings.values() {
-            if !keys.insert(*key) {
+        if !self.profiles.contains_key(&self.profile) {
+            return Err(anyhow!(
+                "the active profile `{}` does not exist",
+                self.profile
+            ));
+        }
+        if let Some(toggle) = self.toggle {
+            if !TOGGLE_HOLD_MS.contains(&toggle.hold_ms) {
                 return Err(anyhow!(
-                    "{} is assigned to more than one action",
-                    key.label()
+                    "the toggle hold time must be between {} and {} ms",
+                    TOGGLE_HOLD_MS.start(),
+                    TOGGLE_HOLD_MS.end()
                 ));
             }
         }
+        // Every profile is checked, not just the active one, so switching can
+        // never fail on a file that loaded.
+        for (name, bindings) in &self.profiles {
+            let mut keys = HashSet::new();
+            for (action, key) in bindings {
+                if self.toggle.is_some_and(|toggle| toggle.key == *key) {
+                    return Err(anyhow!(
+                        "{} is the toggle key and cannot also invoke {} in profile `{name}`",
+                        key.label(),
+                        action.label()
+                    ));
+                }
+                if !keys.insert(*key) {
+                    return Err(anyhow!(
+                        "{} is assigned to more than one action in profile `{name}`",
+                        key.label()
+                    ));
+                }
+            }
+        }
         Ok(())
     }
 
+    /// The bindings of the active profile.
+    pub fn bindings(&self) -> &Bindings {
+        self.profiles.get(&self.profile).unwrap_or(NO_BINDINGS)
+    }
+
+    fn bindings_mut(&mut self) -> &mut Bindings {
+        self.profiles.entry(self.profile.clone()).or_default()
+    }
+
     pub fn key_for(&self, action: Action) -> Option<InputKey> {
-        self.bindings.get(&action).copied()
+        self.bindings().get(&action).copied()
     }
 
     pub fn bind(&mut self, action: Action, key: InputKey) {
-        self.bindings.retain(|_, current| *current != key);
-        self.bindings.insert(action, key);
+        let bindings = self.bindings_mut();
+        bindings.retain(|_, current| *current != key);
+        bindings.insert(action, key);
     }
 
     pub fn unbind(&mut self, action: Action) {
-        self.bindings.remove(&action);
+        self.bindings_mut().remove(&action);
+    }
+
+    /// Replaces the active profile's bindings with the built-in ones.
+    #[cfg(feature = "gui")]
+    pub fn restore_default_bindings(&mut self) {
+        *self.bindings_mut() = Config::default().bindings().clone();
+    }
+
+    pub fn switch_profile(&mut self, name: &str) -> Result<()> {
+        if !self.profiles.contains_key(name) {
+            return Err(ProfileError::Missing(name.to_owned()).into());
+        }
+        self.profile = name.to_owned();
+        Ok(())
+    }
+
+    /// Adds an empty profile and makes it active.
+    pub fn create_profile(&mut self, name: &str) -> Result<()> {
+        let name = self.new_profile_name(name)?;
+        self.profiles.insert(name.clone(), Bindings::new());
+        self.profile = name;
+        Ok(())
+    }
+
+    /// Copies the active profile under `name` and makes the copy active.
+    pub fn duplicate_profile(&mut self, name: &str) -> Result<()> {
+        let name = self.new_profile_name(name)?;
+        self.profiles.insert(name.clone(), self.bindings().clone());
+        self.profile = name;
+        Ok(())
+    }
+
+    pub fn rename_profile(&mut self, name: &str) -> Result<()> {
+        let name = self.new_profile_name(name)?;
+        let bindings = self.profiles.remove(&self.profile).unwrap_or_default();
+        self.profiles.insert(name.clone(), bindings);
+        self.profile = name;
+        Ok(())
+    }
+
+    /// Removes the active profile and activates the first remaining one.
+    pub fn delete_profile(&mut self) -> Result<()> {
+        if self.profiles.len() < 2 {
+            return Err(ProfileError::LastProfile.into());
+        }
+        self.profiles.remove(&self.profile);
+        if let Some(name) = self.profiles.keys().next() {
+            self.profile = name.clone();
+        }
+        Ok(())
+    }
+
+    fn new_profile_name(&self, name: &str) -> Result<String> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(ProfileError::Unnamed.into());
+        }
+        if name.chars().count() > PROFILE_NAME_LIMIT {
+            return Err(ProfileError::TooLong.into());
+        }
+        if self.profiles.contains_key(name) {
+            return Err(ProfileError::Taken(name.to_owned()).into());
+        }
+        Ok(name.to_owned())
     }
 }
 
 struct CompiledBindings {
     enabled: bool,
+    // Compiled even while disabled, since the gesture is how remapping comes back.
+    toggle: Option<ToggleHotkey>,
+    statistics: bool,
     actions: [Option<Action>; InputKey::COUNT],
 }
 
@@ -388,44 +737,250 @@ impl CompiledBindings {
         config.validate()?;
         let mut actions = [None; InputKey::COUNT];
         if config.enabled {
-            for (action, key) in &config.bindings {
+            for (action, key) in config.bindings() {
                 actions[key.index()] = Some(*action);
             }
         }
         Ok(Self {
             enabled: config.enabled,
+            toggle: config.toggle,
+            statistics: config.statistics,
             actions,
         })
     }
 }
 
+/// State changes the keyboard backends report through `RuntimeBindings`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RuntimeEvent {
+    /// The Caps Lock layer was engaged (`true`) or released on every keyboard.
+    Layer(bool),
+    /// A keyboard backend stopped and keys on it are no longer remapped.
+    Failed(String),
+    /// The toggle gesture was completed; remapping should be switched on or off.
+    ToggleRequested,
+    /// The emergency chord released every keyboard; remapping should be saved
+    /// as disabled.
+    EmergencyRelease,
+    /// The key pressed after `begin_capture`, or `None` with the backend's name
+    /// for it when nocaps cannot bind it.
+    Captured { key: Option<InputKey>, name: String },
+}
+
+type Observer = Arc<dyn Fn(RuntimeEvent) + Send + Sync>;
+type Waker = Arc<dyn Fn() + Send + Sync>;
+
 /// Lock-free runtime view used by keyboard hooks. JSON is never consulted on the hot path.
 pub struct RuntimeBindings {
     compiled: ArcSwap<CompiledBindings>,
+    // Bumped by every `replace`. Backends compare it, before handling input or
+    // when `on_change` wakes them, and release outputs they hold under the
+    // previous bindings.
+    generation: AtomicU64,
+    layer_active: AtomicBool,
+    // Set by the emergency chord; keeps remapping off until it is enabled again.
+    released: AtomicBool,
+    // Set by backends that can read keys for the window, and by the window
+    // while it waits for one.
+    capture_available: AtomicBool,
+    capture_requested: AtomicBool,
+    observers: ArcSwap<Vec<Observer>>,
+    wakers: ArcSwap<Vec<Waker>>,
+    usage: Usage,
+    #[cfg(target_os = "linux")]
+    inspector: Inspector,
+    #[cfg(target_os = "linux")]
+    diagnostics: Diagnostics,
 }
 
 impl RuntimeBindings {
     pub fn new(config: &Config) -> Result<Self> {
         Ok(Self {
             compiled: ArcSwap::from_pointee(CompiledBindings::new(config)?),
+            generation: AtomicU64::new(0),
+            layer_active: AtomicBool::new(false),
+            released: AtomicBool::new(false),
+            capture_available: AtomicBool::new(false),
+            capture_requested: AtomicBool::new(false),
+            observers: ArcSwap::from_pointee(Vec::new()),
+            wakers: ArcSwap::from_pointee(Vec::new()),
+            usage: Usage::default(),
+            #[cfg(target_os = "linux")]
+            inspector: Inspector::default(),
+            #[cfg(target_os = "linux")]
+            diagnostics: Diagnostics::default(),
         })
     }
 
+    /// Registers `observer` for every later `RuntimeEvent`. Observers run on the
+    /// input threads, so they must only hand the event off, e.g. through a
+    /// `Handoff`, without locking or allocating.
+    pub fn observe(&self, observer: impl Fn(RuntimeEvent) + Send + Sync + 'static) {
+        let observer: Observer = Arc::new(observer);
+        self.observers.rcu(|observers| {
+            let mut observers = Vec::clone(observers);
+            observers.push(Arc::clone(&observer));
+            observers
+        });
+    }
+
+    /// Registers `wake` to run after every `replace` and emergency release, so
+    /// backends waiting for input apply the change without a key press. It runs
+    /// on the thread that made the change and must not block.
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    pub fn on_change(&self, wake: impl Fn() + Send + Sync + 'static) {
+        let wake: Waker = Arc::new(wake);
+        self.wakers.rcu(|wakers| {
+            let mut wakers = Vec::clone(wakers);
+            wakers.push(Arc::clone(&wake));
+            wakers
+        });
+    }
+
+    fn wake(&self) {
+        for wake in self.wakers.load().iter() {
+            wake();
+        }
+    }
+
+    pub fn is_layer_active(&self) -> bool {
+        self.layer_active.load(Ordering::SeqCst)
+    }
+
+    /// Backends call this as the layer key goes down and up; only transitions
+    /// reach the observers, so key repeat is free.
+    pub fn set_layer_active(&self, active: bool) {
+        if self.layer_active.swap(active, Ordering::SeqCst) != active {
+            self.announce(RuntimeEvent::Layer(active));
+        }
+    }
+
+    /// Backends call this when a worker gives up, after logging the error, so
+    /// the tray and window can show that remapping has stopped.
+    pub fn report_failure(&self, message: String) {
+        self.announce(RuntimeEvent::Failed(message));
+    }
+
+    /// Backends call this once per completed toggle gesture. The configuration
+    /// is changed by whoever observes it, never on the input thread.
+    pub fn request_toggle(&self) {
+        self.announce(RuntimeEvent::ToggleRequested);
+    }
+
+    /// Backends call this for the emergency chord. Remapping stops at once,
+    /// before the configuration catches up, and stays off until a change
+    /// switches it from disabled back to enabled.
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    pub fn emergency_release(&self) {
+        if !self.released.swap(true, Ordering::SeqCst) {
+            self.wake();
+            self.announce(RuntimeEvent::EmergencyRelease);
+        }
+    }
+
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    pub fn is_released(&self) -> bool {
+        self.released.load(Ordering::SeqCst)
+    }
+
+    /// Backends that see every key press they remap call this once they run,
+    /// and again with `false` if they stop.
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    pub fn offer_capture(&self, available: bool) {
+        self.capture_available.store(available, Ordering::SeqCst);
+    }
+
+    /// Backends offer every key press here. Returns true when it was taken for
+    /// a capture, in which case it should not be typed; `name` is only asked
+    /// for then.
+    #[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+    pub fn capture(&self, key: Option<InputKey>, name: impl FnOnce() -> String) -> bool {
+        if !self.capture_requested.load(Ordering::Relaxed)
+            || !self.capture_requested.swap(false, Ordering::SeqCst)
+        {
+            return false;
+        }
+        self.announce(RuntimeEvent::Captured { key, name: name() });
+        true
+    }
+
+    fn announce(&self, event: RuntimeEvent) {
+        for observer in self.observers.load().iter() {
+            observer(event.clone());
+        }
+    }
+
+    /// Backends call this when a layer key first invokes `action`; repeats
+    /// and releases are not counted. Does nothing unless statistics are on.
+    pub fn record_use(&self, key: InputKey, action: Action) {
+        if self.compiled.load().statistics {
+            self.usage.record(key, action);
+        }
+    }
+
+    pub fn usage(&self) -> &Usage {
+        &self.usage
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn inspector(&self) -> &Inspector {
+        &self.inspector
+    }
+
+    #[cfg(target_os = "linux")]
+    pub fn diagnostics(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+
     pub fn action_for(&self, key: InputKey) -> Option<Action> {
         self.compiled.load().actions[key.index()]
     }
 
     pub fn is_enabled(&self) -> bool {
-        self.compiled.load().enabled
+        self.compiled.load().enabled && !self.is_released()
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    pub fn toggle_hotkey(&self) -> Option<ToggleHotkey> {
+        self.compiled.load().toggle
     }
 
     pub fn replace(&self, config: &Config) -> Result<()> {
-        self.compiled
-            .store(Arc::new(CompiledBindings::new(config)?));
+        let previous = self.compiled.swap(Arc::new(CompiledBindings::new(config)?));
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        // Other edits made while released, such as a rebinding, keep it released.
+        if config.enabled && !previous.enabled {
+            self.released.store(false, Ordering::SeqCst);
+        }
+        self.wake();
         Ok(())
     }
 }
 
+/// Capturing a key for a binding, asked for by the window.
+#[cfg(feature = "gui")]
+impl RuntimeBindings {
+    /// Whether `begin_capture` will be answered with `RuntimeEvent::Captured`.
+    /// Only grabbed keyboards offer their keys, and they are grabbed only
+    /// while remapping is enabled.
+    pub fn can_capture(&self) -> bool {
+        self.capture_available.load(Ordering::SeqCst) && self.is_enabled()
+    }
+
+    /// Asks the backend for the next key pressed on any keyboard, so a binding
+    /// is made with exactly the key the remapper will see.
+    pub fn begin_capture(&self) {
+        self.capture_requested.store(true, Ordering::SeqCst);
+    }
+
+    pub fn cancel_capture(&self) {
+        self.capture_requested.store(false, Ordering::SeqCst);
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct ConfigStore {
     path: PathBuf,
@@ -440,23 +995,54 @@ impl ConfigStore {
         })
     }
 
+    #[cfg(test)]
+    pub fn at(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
     pub fn path(&self) -> &Path {
         &self.path
     }
 
+    /// Where a running instance keeps its lock and control socket: the per-user
+    /// runtime directory when the OS has one, otherwise the configuration directory.
+    pub fn runtime_dir(&self) -> Result<PathBuf> {
+        let base = BaseDirs::new();
+        base.as_ref()
+            .and_then(BaseDirs::runtime_dir)
+            .or_else(|| self.path.parent())
+            .map(Path::to_owned)
+            .ok_or_else(|| anyhow!("configuration path has no parent"))
+    }
+
     pub fn load_or_create(&self) -> Result<Config> {
         if !self.path.exists() {
             let config = Config::default();
             self.save(&config)?;
             return Ok(config);
         }
+        self.load()
+    }
 
+    pub fn load(&self) -> Result<Config> {
         let bytes = fs::read(&self.path)
             .with_context(|| format!("read configuration from {}", self.path.display()))?;
-        let config: Config = serde_json::from_slice(&bytes)
+        let upgrade = migration::upgrade(&bytes)
             .with_context(|| format!("parse configuration from {}", self.path.display()))?;
-        config.validate()?;
-        Ok(config)
+        upgrade.config.validate()?;
+        if let Some(version) = upgrade.upgraded_from {
+            // Keep the file exactly as the older release wrote it before replacing it.
+            let backup = self.path.with_extension(format!("json.v{version}.bak"));
+            fs::write(&backup, &bytes)
+                .with_context(|| format!("back up configuration to {}", backup.display()))?;
+            self.save(&upgrade.config)?;
+            log::info!(
+                "upgraded {} from version {version}; the original is kept at {}",
+                self.path.display(),
+                backup.display()
+            );
+        }
+        Ok(upgrade.config)
     }
 
     pub fn save(&self, config: &Config) -> Result<()> {
@@ -483,6 +1069,83 @@ mod tests {
         assert_eq!(runtime.action_for(InputKey::J), Some(Action::ArrowLeft));
     }
 
+    #[test]
+    fn key_and_action_tables_follow_the_discriminants() {
+        assert!(InputKey::ALL
+            .iter()
+            .enumerate()
+            .all(|(i, key)| key.index() == i));
+        assert!(Action::ALL
+            .iter()
+            .enumerate()
+            .all(|(i, action)| action.index() == i));
+    }
+
+    #[test]
+    fn usage_is_only_counted_when_statistics_are_on() {
+        let mut config = Config::default();
+        let runtime = RuntimeBindings::new(&config).unwrap();
+        runtime.record_use(InputKey::I, Action::ArrowUp);
+        assert!(runtime.usage().counts().actions.is_empty());
+
+        config.statistics = true;
+        runtime.replace(&config).unwrap();
+        runtime.record_use(InputKey::I, Action::ArrowUp);
+        assert_eq!(runtime.usage().counts().keys[&InputKey::I], 1);
+    }
+
+    #[test]
+    #[cfg(feature = "gui")]
+    fn a_capture_takes_exactly_one_key_press() {
+        let runtime = RuntimeBindings::new(&Config::default()).unwrap();
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        runtime.observe(move |event| sink.lock().unwrap().push(event));
+
+        assert!(!runtime.capture(Some(InputKey::A), || "KEY_A".to_owned()));
+        runtime.begin_capture();
+        assert!(runtime.capture(None, || "KEY_KP1".to_owned()));
+        assert!(!runtime.capture(Some(InputKey::B), || "KEY_B".to_owned()));
+        runtime.begin_capture();
+        runtime.cancel_capture();
+        assert!(!runtime.capture(Some(InputKey::C), || "KEY_C".to_owned()));
+        assert_eq!(
+            *seen.lock().unwrap(),
+            [RuntimeEvent::Captured {
+                key: None,
+                name: "KEY_KP1".to_owned()
+            }]
+        );
+
+        runtime.offer_capture(true);
+        assert!(runtime.can_capture());
+        // Keyboards left to the desktop leave the capture to the window.
+        runtime
+            .replace(&Config {
+                enabled: false,
+                ..Config::default()
+            })
+            .unwrap();
+        assert!(!runtime.can_capture());
+    }
+
+    #[test]
+    fn observers_see_layer_transitions_once() {
+        let runtime = RuntimeBindings::new(&Config::default()).unwrap();
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        runtime.observe(move |event| sink.lock().unwrap().push(event));
+
+        runtime.set_layer_active(true);
+        runtime.set_layer_active(true);
+        runtime.set_layer_active(false);
+        assert!(!runtime.is_layer_active());
+        assert_eq!(
+            *seen.lock().unwrap(),
+            [RuntimeEvent::Layer(true), RuntimeEvent::Layer(false)]
+        );
+    }
+
     #[test]
     fn rebinding_a_key_removes_its_previous_action() {
         let mut config = Config::default();
@@ -492,6 +1155,44 @@ mod tests {
         assert!(config.validate().is_ok());
     }
 
+    #[test]
+    fn emergency_release_lasts_until_remapping_is_enabled_again() {
+        let mut config = Config::default();
+        let runtime = RuntimeBindings::new(&config).unwrap();
+        runtime.emergency_release();
+        assert!(!runtime.is_enabled());
+
+        config.bind(Action::PageUp, InputKey::P);
+        runtime.replace(&config).unwrap();
+        assert!(!runtime.is_enabled());
+
+        config.enabled = false;
+        runtime.replace(&config).unwrap();
+        config.enabled = true;
+        runtime.replace(&config).unwrap();
+        assert!(runtime.is_enabled());
+        assert_eq!(runtime.generation(), 3);
+    }
+
+    #[test]
+    fn profiles_keep_separate_bindings() {
+        let mut config = Config::default();
+        config.duplicate_profile("presentation").unwrap();
+        config.bind(Action::PageDown, InputKey::K);
+        assert_eq!(config.key_for(Action::ArrowDown), None);
+
+        config.switch_profile("default").unwrap();
+        assert_eq!(config.key_for(Action::ArrowDown), Some(InputKey::K));
+        assert_eq!(config.key_for(Action::PageDown), None);
+        assert!(config.create_profile(" presentation ").is_err());
+        assert!(config.switch_profile("missing").is_err());
+
+        config.delete_profile().unwrap();
+        assert_eq!(config.profile, "presentation");
+        assert!(config.delete_profile().is_err());
+        config.validate().unwrap();
+    }
+
     #[test]
     fn json_is_action_first_and_readable() {
         let json = serde_json::to_string_pretty(&Config::default()).unwrap();
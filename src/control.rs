@@ -0,0 +1,242 @@
+use crate::config::ConfigStore;
+use crate::controller::{Controller, Notice};
+use anyhow::{anyhow, Context, Result};
+use serde_json::{json, Value};
+use std::fs;
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+const SOCKET_NAME: &str = "nocaps.sock";
+// A client that connects and then goes quiet must not block everyone after it.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(5);
+
+const REQUESTS: &str = "status, enable, disable, toggle, reload, show, profile <name>";
+
+/// Requests accepted on the control socket, one per line.
+#[derive(Debug, PartialEq)]
+enum Request {
+    Status,
+    Enable,
+    Disable,
+    Toggle,
+    Reload,
+    Show,
+    Profile(String),
+}
+
+impl Request {
+    fn parse(line: &str) -> Result<Self> {
+        // Profile names may contain spaces, so the name is the rest of the line.
+        if let Some(("profile", name)) = line.trim().split_once(char::is_whitespace) {
+            return Ok(Self::Profile(name.trim().to_owned()));
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        Ok(match words.as_slice() {
+            ["status"] => Self::Status,
+            ["enable"] => Self::Enable,
+            ["disable"] => Self::Disable,
+            ["toggle"] => Self::Toggle,
+            ["reload"] => Self::Reload,
+            ["show"] => Self::Show,
+            _ => {
+                return Err(anyhow!(
+                    "unknown request `{}`; expected one of: {REQUESTS}",
+                    line.trim()
+                ))
+            }
+        })
+    }
+}
+
+/// Serves the control socket of a running instance. The socket file is removed
+/// when this value is dropped.
+pub struct ControlServer {
+    path: PathBuf,
+}
+
+impl ControlServer {
+    pub fn start(controller: Arc<Controller>) -> Result<Self> {
+        let path = socket_path(controller.store())?;
+        let listener = bind(&path)?;
+        thread::Builder::new()
+            .name("nocaps-control".to_owned())
+            .spawn(move || serve(listener, &controller))
+            .context("start control socket server")?;
+        log::info!("control socket listening at {}", path.display());
+        Ok(Self { path })
+    }
+}
+
+impl Drop for ControlServer {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// `nocaps.sock` in the runtime directory, usually `$XDG_RUNTIME_DIR`.
+pub fn socket_path(store: &ConfigStore) -> Result<PathBuf> {
+    Ok(store.runtime_dir()?.join(SOCKET_NAME))
+}
+
+/// Sends one request to the running instance and returns its reply.
+pub fn request(store: &ConfigStore, request: &str) -> Result<Value> {
+    let path = socket_path(store)?;
+    let stream =
+        UnixStream::connect(&path).with_context(|| format!("connect to {}", path.display()))?;
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    writeln!(&stream, "{request}")?;
+    let mut reply = String::new();
+    BufReader::new(&stream).read_line(&mut reply)?;
+    serde_json::from_str(&reply).context("read control socket reply")
+}
+
+fn bind(path: &Path) -> Result<UnixListener> {
+    let listener = match UnixListener::bind(path) {
+        Ok(listener) => listener,
+        Err(error) if error.kind() == ErrorKind::AddrInUse => {
+            if UnixStream::connect(path).is_ok() {
+                return Err(anyhow!(
+                    "another nocaps is already listening at {}",
+                    path.display()
+                ));
+            }
+            // Left behind by an instance that did not exit cleanly.
+            fs::remove_file(path)
+                .with_context(|| format!("remove stale socket {}", path.display()))?;
+            UnixListener::bind(path).with_context(|| format!("listen at {}", path.display()))?
+        }
+        Err(error) => {
+            return Err(error).with_context(|| format!("listen at {}", path.display()));
+        }
+    };
+    // The socket can toggle a keyboard hook; keep it to this user even when it
+    // falls back to a directory other users can read.
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))
+        .with_context(|| format!("restrict access to {}", path.display()))?;
+    Ok(listener)
+}
+
+fn serve(listener: UnixListener, controller: &Controller) {
+    for stream in listener.incoming() {
+        let result = stream.and_then(|stream| handle(stream, controller));
+        if let Err(error) = result {
+            log::debug!("control client dropped: {error}");
+        }
+    }
+}
+
+fn handle(stream: UnixStream, controller: &Controller) -> io::Result<()> {
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT))?;
+    let mut writer = &stream;
+    for line in BufReader::new(&stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = Request::parse(&line)
+            .and_then(|request| respond(request, controller))
+            .unwrap_or_else(|error| json!({ "ok": false, "error": format!("{error:#}") }));
+        writeln!(writer, "{reply}")?;
+    }
+    Ok(())
+}
+
+/// Carries out `request` and replies with the resulting state, so every answer
+/// tells the client where things stand.
+fn respond(request: Request, controller: &Controller) -> Result<Value> {
+    match request {
+        Request::Status => {}
+        Request::Enable => set_enabled(controller, |_| true)?,
+        Request::Disable => set_enabled(controller, |_| false)?,
+        Request::Toggle => set_enabled(controller, |enabled| !enabled)?,
+        Request::Reload => controller.reload()?,
+        Request::Show => controller.notify(&Notice::ShowWindow),
+        Request::Profile(name) => controller.update(|config| {
+            config.switch_profile(&name)?;
+            Ok(format!("profile {name} — selected from the control socket"))
+        })?,
+    }
+    let config = controller.config();
+    Ok(json!({
+        "ok": true,
+        "enabled": config.enabled,
+        "profile": config.profile,
+        "config": controller.store().path(),
+        "version": env!("CARGO_PKG_VERSION"),
+    }))
+}
+
+fn set_enabled(controller: &Controller, next: impl FnOnce(bool) -> bool) -> Result<()> {
+    controller.update(|config| {
+        config.enabled = next(config.enabled);
+        Ok(if config.enabled {
+            "no caps — enabled from the control socket".to_owned()
+        } else {
+            "caps — disabled from the control socket".to_owned()
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, RuntimeBindings};
+    use std::net::Shutdown;
+
+    #[test]
+    fn requests_are_single_words() {
+        assert_eq!(Request::parse("status").unwrap(), Request::Status);
+        assert_eq!(Request::parse("  toggle \r").unwrap(), Request::Toggle);
+        assert!(Request::parse("enable now").is_err());
+        assert!(Request::parse("quit").is_err());
+    }
+
+    #[test]
+    fn profile_requests_name_the_profile() {
+        assert_eq!(
+            Request::parse("profile gaming").unwrap(),
+            Request::Profile("gaming".to_owned())
+        );
+        assert_eq!(
+            Request::parse("profile  Coding Setup \r").unwrap(),
+            Request::Profile("Coding Setup".to_owned())
+        );
+        assert!(Request::parse("profile").is_err());
+    }
+
+    #[test]
+    fn clients_change_the_shared_configuration_and_read_it_back() {
+        let directory = std::env::temp_dir().join(format!("nocaps-control-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let config = Config::default();
+        let runtime = Arc::new(RuntimeBindings::new(&config).unwrap());
+        let store = ConfigStore::at(directory.join("config.json"));
+        let controller = Controller::new(Arc::clone(&runtime), store, config);
+        let path = directory.join(SOCKET_NAME);
+        let listener = bind(&path).unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            handle(stream, &controller).unwrap();
+        });
+
+        let mut client = UnixStream::connect(&path).unwrap();
+        writeln!(client, "disable\nstatus\nfrobnicate").unwrap();
+        client.shutdown(Shutdown::Write).unwrap();
+        let replies: Vec<Value> = BufReader::new(client)
+            .lines()
+            .map(|line| serde_json::from_str(&line.unwrap()).unwrap())
+            .collect();
+        server.join().unwrap();
+        fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(replies[0]["enabled"], false);
+        assert_eq!(replies[1]["ok"], true);
+        assert_eq!(replies[2]["ok"], false);
+        assert!(!runtime.is_enabled());
+    }
+}
@@ -0,0 +1,216 @@
+use crate::config::{Config, ConfigStore, RuntimeBindings, RuntimeEvent};
+use crate::handoff::Handoff;
+use anyhow::Result;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Announces a change to the shared configuration so every surface showing it
+/// (window, tray, control clients) can catch up.
+#[derive(Clone)]
+pub enum Notice {
+    /// The configuration in effect changed; the text says how, for a status line.
+    Changed(String),
+    /// `config.json` was edited outside nocaps and now applies.
+    Reloaded,
+    /// `config.json` was edited outside nocaps but could not be applied, so the
+    /// previous configuration still applies; the text says why.
+    NotReloaded(String),
+    /// The `change` applies but could not be saved, so it is lost when nocaps
+    /// exits; `error` says why.
+    Unsaved { change: String, error: String },
+    /// Another launch of nocaps asked this instance to bring up its window.
+    ShowWindow,
+}
+
+// The gestures `follow_gestures` hands to its thread.
+const TOGGLE: u32 = 1;
+const EMERGENCY: u32 = 1 << 1;
+
+type Listener = Box<dyn Fn(&Notice) + Send + Sync>;
+
+/// Single owner of the configuration in effect. The window, the file watcher and
+/// the control socket all change bindings through here, so each change is
+/// compiled into `RuntimeBindings`, saved through `ConfigStore` and announced once.
+pub struct Controller {
+    runtime: Arc<RuntimeBindings>,
+    store: ConfigStore,
+    config: Mutex<Config>,
+    listeners: Mutex<Vec<Listener>>,
+}
+
+impl Controller {
+    pub fn new(runtime: Arc<RuntimeBindings>, store: ConfigStore, config: Config) -> Self {
+        Self {
+            runtime,
+            store,
+            config: Mutex::new(config),
+            listeners: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn store(&self) -> &ConfigStore {
+        &self.store
+    }
+
+    pub fn config(&self) -> Config {
+        self.lock().clone()
+    }
+
+    /// Registers `listener` for every later notice. Listeners run on the thread
+    /// that made the change, so they should only queue work and wake their owner.
+    pub fn subscribe(&self, listener: impl Fn(&Notice) + Send + Sync + 'static) {
+        if let Ok(mut listeners) = self.listeners.lock() {
+            listeners.push(Box::new(listener));
+        }
+    }
+
+    /// Edits the configuration in effect under the lock, so concurrent edits from
+    /// different surfaces cannot overwrite each other. `change` returns the status
+    /// text, or an error that leaves the configuration untouched.
+    pub fn update(&self, change: impl FnOnce(&mut Config) -> Result<String>) -> Result<()> {
+        let current = self.lock();
+        let mut config = current.clone();
+        let message = change(&mut config)?;
+        self.commit(current, config, message)
+    }
+
+    /// Saves the effect of the keyboard gestures: the toggle switches remapping
+    /// on or off, the emergency chord switches it off. Backends report them on
+    /// input threads, which must never wait on the disk, so the change is made
+    /// and saved on a thread of its own.
+    pub fn follow_gestures(self: &Arc<Self>) -> Result<()> {
+        let controller = Arc::clone(self);
+        let handoff = Handoff::spawn("nocaps-gestures", move |flags| {
+            let result = controller.update(|config| {
+                if flags & EMERGENCY != 0 {
+                    config.enabled = false;
+                    return Ok("caps — every keyboard released by the emergency chord".to_owned());
+                }
+                config.enabled = !config.enabled;
+                Ok(if config.enabled {
+                    "no caps — enabled from the keyboard".to_owned()
+                } else {
+                    "caps — disabled from the keyboard".to_owned()
+                })
+            });
+            if let Err(error) = result {
+                log::warn!("could not apply a keyboard gesture: {error:#}");
+            }
+        })?;
+        self.runtime.observe(move |event| match event {
+            RuntimeEvent::ToggleRequested => handoff.raise(TOGGLE),
+            RuntimeEvent::EmergencyRelease => handoff.raise(EMERGENCY),
+            _ => {}
+        });
+        Ok(())
+    }
+
+    /// Re-reads the configuration file and applies it if it differs.
+    pub fn reload(&self) -> Result<()> {
+        self.adopt(self.store.load())
+    }
+
+    /// Applies a configuration that was just read from disk, without writing it
+    /// back. The file watcher also reads back our own saves; those are ignored.
+    /// A file that failed to load leaves the running configuration in place.
+    pub fn adopt(&self, loaded: Result<Config>) -> Result<()> {
+        let mut current = self.lock();
+        let applied = loaded.and_then(|config| {
+            if *current != config {
+                self.runtime.replace(&config)?;
+                *current = config;
+                return Ok(true);
+            }
+            Ok(false)
+        });
+        drop(current);
+        match &applied {
+            Ok(false) => {}
+            Ok(true) => self.notify(&Notice::Reloaded),
+            Err(error) => self.notify(&Notice::NotReloaded(format!("{error:#}"))),
+        }
+        applied.map(|_| ())
+    }
+
+    pub fn notify(&self, notice: &Notice) {
+        if let Ok(listeners) = self.listeners.lock() {
+            for listener in listeners.iter() {
+                listener(notice);
+            }
+        }
+    }
+
+    fn commit(
+        &self,
+        mut current: MutexGuard<'_, Config>,
+        config: Config,
+        message: String,
+    ) -> Result<()> {
+        self.runtime.replace(&config)?;
+        let saved = self.store.save(&config);
+        *current = config;
+        // Listeners may read the configuration back, so announce outside the lock.
+        drop(current);
+        self.notify(&match &saved {
+            Ok(()) => Notice::Changed(message),
+            // In effect until nocaps exits, but lost then; not a success.
+            Err(error) => Notice::Unsaved {
+                change: message,
+                error: format!("{error:#}"),
+            },
+        });
+        saved
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Config> {
+        // The configuration is only ever replaced whole, so a lock poisoned by a
+        // panicking holder still guards a valid value.
+        self.config
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[cfg(feature = "gui")]
+impl Controller {
+    pub fn runtime(&self) -> &Arc<RuntimeBindings> {
+        &self.runtime
+    }
+
+    /// Makes `config` the configuration in effect and saves it.
+    pub fn apply(&self, config: Config, message: impl Into<String>) -> Result<()> {
+        self.commit(self.lock(), config, message.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn a_change_that_cannot_be_saved_is_not_announced_as_done() {
+        // A file where the configuration directory should be.
+        let blocker = std::env::temp_dir().join(format!("nocaps-unsaved-{}", std::process::id()));
+        fs::write(&blocker, "").unwrap();
+        let config = Config::default();
+        let runtime = Arc::new(RuntimeBindings::new(&config).unwrap());
+        let store = ConfigStore::at(blocker.join("config.json"));
+        let controller = Controller::new(Arc::clone(&runtime), store, config);
+        let notices = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&notices);
+        controller.subscribe(move |notice| sink.lock().unwrap().push(notice.clone()));
+
+        let result = controller.update(|config| {
+            config.enabled = false;
+            Ok("caps".to_owned())
+        });
+        fs::remove_file(&blocker).unwrap();
+
+        assert!(result.is_err());
+        assert!(!runtime.is_enabled());
+        assert!(matches!(
+            notices.lock().unwrap().as_slice(),
+            [Notice::Unsaved { change, .. }] if change == "caps"
+        ));
+    }
+}
@@ -0,0 +1,304 @@
+use crate::config::{RuntimeBindings, RuntimeEvent};
+use crate::controller::{Controller, Notice};
+use crate::handoff::Handoff;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::sync::Arc;
+use zbus::blocking::connection::{self, Connection};
+use zbus::zvariant::Value;
+use zbus::{fdo, interface};
+
+const BUS_NAME: &str = "org.nocaps.Remapper";
+const OBJECT_PATH: &str = "/org/nocaps/Remapper";
+const INTERFACE: &str = "org.nocaps.Remapper";
+
+/// The object desktop extensions talk to. Reads come straight from the
+/// controller and the runtime; writes go through the controller like every
+/// other surface.
+struct Remapper {
+    controller: Arc<Controller>,
+    runtime: Arc<RuntimeBindings>,
+}
+
+#[interface(name = "org.nocaps.Remapper")]
+impl Remapper {
+    fn enable(&self) -> fdo::Result<()> {
+        set_enabled(&self.controller, true)
+    }
+
+    fn disable(&self) -> fdo::Result<()> {
+        set_enabled(&self.controller, false)
+    }
+
+    fn reload(&self) -> fdo::Result<()> {
+        self.controller.reload().map_err(failed)
+    }
+
+    fn set_profile(&self, name: &str) -> fdo::Result<()> {
+        if !self.controller.config().profiles.contains_key(name) {
+            return Err(fdo::Error::InvalidArgs(format!(
+                "there is no profile named `{name}`"
+            )));
+        }
+        self.controller
+            .update(|config| {
+                config.switch_profile(name)?;
+                Ok(format!("profile {name} — selected over D-Bus"))
+            })
+            .map_err(failed)
+    }
+
+    #[zbus(property)]
+    fn enabled(&self) -> bool {
+        self.controller.config().enabled
+    }
+
+    #[zbus(property)]
+    fn layer_active(&self) -> bool {
+        self.runtime.is_layer_active()
+    }
+
+    #[zbus(property)]
+    fn profile(&self) -> String {
+        self.controller.config().profile
+    }
+}
+
+// The changes handed to the signal thread.
+const CONFIG: u32 = 1;
+const LAYER: u32 = 1 << 1;
+
+/// Publishes `org.nocaps.Remapper` at `/org/nocaps/Remapper` on the session bus.
+pub struct DbusService {
+    _connection: Connection,
+}
+
+impl DbusService {
+    pub fn start(controller: Arc<Controller>, runtime: Arc<RuntimeBindings>) -> Result<Self> {
+        let builder = connection::Builder::session().context("connect to the session bus")?;
+        Self::serve(builder, controller, runtime)
+    }
+
+    fn serve(
+        builder: connection::Builder<'_>,
+        controller: Arc<Controller>,
+        runtime: Arc<RuntimeBindings>,
+    ) -> Result<Self> {
+        let remapper = Remapper {
+            controller: Arc::clone(&controller),
+            runtime: Arc::clone(&runtime),
+        };
+        let connection = builder
+            .name(BUS_NAME)?
+            .serve_at(OBJECT_PATH, remapper)?
+            .build()
+            .with_context(|| format!("publish {BUS_NAME}"))?;
+
+        // Notices and layer events arrive on the threads that caused them, the
+        // input threads among them, so they are only flagged here.
+        let emitter = connection.clone();
+        let mut announcer = Announcer::new(&controller, &runtime);
+        let handoff = Arc::new(Handoff::spawn("nocaps-dbus", move |flags| {
+            announcer.announce(&emitter, flags)
+        })?);
+        let config_changes = Arc::clone(&handoff);
+        controller.subscribe(move |notice| {
+            // Unsaved too: a change that could not be saved still applies.
+            if let Notice::Changed(_) | Notice::Reloaded | Notice::Unsaved { .. } = notice {
+                config_changes.raise(CONFIG);
+            }
+        });
+        runtime.observe(move |event| {
+            if let RuntimeEvent::Layer(_) = event {
+                handoff.raise(LAYER);
+            }
+        });
+
+        log::info!("published {BUS_NAME} on D-Bus");
+        Ok(Self {
+            _connection: connection,
+        })
+    }
+}
+
+/// The property values last announced, so each `PropertiesChanged` lists only
+/// the properties whose values actually moved.
+struct Announcer {
+    controller: Arc<Controller>,
+    runtime: Arc<RuntimeBindings>,
+    enabled: bool,
+    profile: String,
+    layer_active: bool,
+}
+
+impl Announcer {
+    fn new(controller: &Arc<Controller>, runtime: &Arc<RuntimeBindings>) -> Self {
+        let config = controller.config();
+        Self {
+            controller: Arc::clone(controller),
+            runtime: Arc::clone(runtime),
+            enabled: config.enabled,
+            profile: config.profile,
+            layer_active: runtime.is_layer_active(),
+        }
+    }
+
+    fn announce(&mut self, connection: &Connection, flags: u32) {
+        let mut changed = HashMap::new();
+        if flags & CONFIG != 0 {
+            let config = self.controller.config();
+            if config.enabled != self.enabled {
+                self.enabled = config.enabled;
+                changed.insert("Enabled", Value::from(self.enabled));
+            }
+            if config.profile != self.profile {
+                self.profile = config.profile;
+                changed.insert("Profile", Value::from(self.profile.clone()));
+            }
+        }
+        if flags & LAYER != 0 {
+            let active = self.runtime.is_layer_active();
+            if active != self.layer_active {
+                self.layer_active = active;
+                changed.insert("LayerActive", Value::from(active));
+            }
+        }
+        if changed.is_empty() {
+            return;
+        }
+        let result = connection.emit_signal(
+            None::<&str>,
+            OBJECT_PATH,
+            "org.freedesktop.DBus.Properties",
+            "PropertiesChanged",
+            &(INTERFACE, changed, Vec::<&str>::new()),
+        );
+        if let Err(error) = result {
+            log::warn!("could not announce a D-Bus property change: {error}");
+        }
+    }
+}
+
+fn set_enabled(controller: &Controller, enabled: bool) -> fdo::Result<()> {
+    controller
+        .update(|config| {
+            config.enabled = enabled;
+            Ok(if enabled {
+                "no caps — enabled over D-Bus".to_owned()
+            } else {
+                "caps — disabled over D-Bus".to_owned()
+            })
+        })
+        .map_err(failed)
+}
+
+fn failed(error: anyhow::Error) -> fdo::Error {
+    fdo::Error::Failed(format!("{error:#}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Action, Config, ConfigStore, InputKey};
+    use std::fs;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+    use zbus::blocking::Proxy;
+    use zbus::zvariant::OwnedValue;
+
+    /// A bus of our own, so the test neither needs nor disturbs a desktop session.
+    struct PrivateBus {
+        daemon: Child,
+        address: String,
+    }
+
+    impl PrivateBus {
+        fn start() -> Option<Self> {
+            let mut daemon = Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .ok()?;
+            let mut address = String::new();
+            BufReader::new(daemon.stdout.as_mut()?)
+                .read_line(&mut address)
+                .ok()?;
+            Some(Self {
+                daemon,
+                address: address.trim().to_owned(),
+            })
+        }
+    }
+
+    impl Drop for PrivateBus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    type PropertiesChanged = (String, HashMap<String, OwnedValue>, Vec<String>);
+
+    #[test]
+    fn extensions_toggle_and_observe_the_remapper() {
+        let Some(bus) = PrivateBus::start() else {
+            eprintln!("dbus-daemon is not installed; skipping");
+            return;
+        };
+        let directory = std::env::temp_dir().join(format!("nocaps-dbus-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let mut config = Config::default();
+        config.duplicate_profile("presentation").unwrap();
+        config.bind(Action::PageDown, InputKey::K);
+        config.switch_profile("default").unwrap();
+        let runtime = Arc::new(RuntimeBindings::new(&config).unwrap());
+        let store = ConfigStore::at(directory.join("config.json"));
+        let controller = Arc::new(Controller::new(Arc::clone(&runtime), store, config));
+        let builder = connection::Builder::address(bus.address.as_str()).unwrap();
+        let _service = DbusService::serve(builder, controller, Arc::clone(&runtime)).unwrap();
+
+        let client = connection::Builder::address(bus.address.as_str())
+            .unwrap()
+            .build()
+            .unwrap();
+        let remapper = Proxy::new(&client, BUS_NAME, OBJECT_PATH, INTERFACE).unwrap();
+        let properties = Proxy::new(
+            &client,
+            BUS_NAME,
+            OBJECT_PATH,
+            "org.freedesktop.DBus.Properties",
+        )
+        .unwrap();
+        let mut signals = properties.receive_signal("PropertiesChanged").unwrap();
+        let mut next_change = || {
+            let (_, changed, _): PropertiesChanged =
+                signals.next().unwrap().body().deserialize().unwrap();
+            changed
+        };
+
+        assert_eq!(
+            remapper.get_property::<String>("Profile").unwrap(),
+            "default"
+        );
+        remapper
+            .call::<_, _, ()>("SetProfile", &("presentation",))
+            .unwrap();
+        assert_eq!(runtime.action_for(InputKey::K), Some(Action::PageDown));
+        assert_eq!(
+            next_change()["Profile"],
+            OwnedValue::try_from(Value::from("presentation")).unwrap()
+        );
+        assert!(remapper
+            .call::<_, _, ()>("SetProfile", &("missing",))
+            .is_err());
+
+        remapper.call::<_, _, ()>("Disable", &()).unwrap();
+        assert!(!runtime.is_enabled());
+        assert_eq!(next_change()["Enabled"], OwnedValue::from(false));
+
+        runtime.set_layer_active(true);
+        assert_eq!(next_change()["LayerActive"], OwnedValue::from(true));
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
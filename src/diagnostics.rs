@@ -0,0 +1,292 @@
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// What the Linux backend found when it started and how its workers are
+/// doing, for the diagnostics panel and bug reports. Written once at startup
+/// and on the rare state changes; nothing here is on the per-event path.
+#[derive(Default)]
+pub struct Diagnostics {
+    devices: Mutex<Vec<DeviceReport>>,
+    output: Mutex<Option<OutputReport>>,
+    // The heartbeat the watchdog gave up on, once it has.
+    stalled: Mutex<Option<String>>,
+}
+
+/// One event node under `/dev/input`.
+#[derive(Clone)]
+pub struct DeviceReport {
+    pub path: String,
+    /// Empty when the node could not be opened.
+    pub name: String,
+    /// Bus, vendor and product, the way lsusb prints the last two.
+    pub id: String,
+    pub included: bool,
+    /// Why the device was taken or left alone.
+    pub reason: String,
+    worker: Option<Arc<Worker>>,
+}
+
+/// The virtual keyboard every remapped key is written to.
+#[derive(Clone)]
+pub struct OutputReport {
+    pub name: String,
+    /// Its node under `/dev/input`, if udev had made one by the time it was asked.
+    pub path: Option<String>,
+    /// Every key code it can emit, in order.
+    pub keys: Vec<u16>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WorkerState {
+    /// Spawned, but not yet waiting for input.
+    Starting,
+    /// Holding the keyboard and remapping it.
+    Grabbed,
+    /// Leaving the keyboard to the desktop while remapping is disabled.
+    Released,
+    /// Exited after a read or write error.
+    Stopped,
+    /// Stopped because the watchdog let every keyboard go.
+    Abandoned,
+    /// Exited because the keyboard was unplugged.
+    Unplugged,
+}
+
+const STATES: [WorkerState; 6] = [
+    WorkerState::Starting,
+    WorkerState::Grabbed,
+    WorkerState::Released,
+    WorkerState::Stopped,
+    WorkerState::Abandoned,
+    WorkerState::Unplugged,
+];
+
+/// Shared between a keyboard's worker, which sets it, and its report.
+#[derive(Default)]
+pub struct Worker {
+    state: AtomicU8,
+    error: Mutex<Option<String>>,
+}
+
+impl Worker {
+    pub fn set(&self, state: WorkerState) {
+        self.state.store(state as u8, Ordering::Relaxed);
+    }
+
+    pub fn stop(&self, error: String) {
+        *self.error.lock().unwrap_or_else(PoisonError::into_inner) = Some(error);
+        self.set(WorkerState::Stopped);
+    }
+
+    pub fn state(&self) -> WorkerState {
+        STATES[usize::from(self.state.load(Ordering::Relaxed))]
+    }
+
+    pub fn error(&self) -> Option<String> {
+        self.error
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+impl DeviceReport {
+    pub fn new(path: String, name: String, id: String, included: bool, reason: String) -> Self {
+        Self {
+            path,
+            name,
+            id,
+            included,
+            reason,
+            worker: None,
+        }
+    }
+
+    /// Gives the worker for this device the handle it reports through.
+    pub fn watch(&mut self) -> Arc<Worker> {
+        Arc::clone(self.worker.get_or_insert_with(Arc::default))
+    }
+
+    /// `None` for devices that never had a worker.
+    pub fn worker(&self) -> Option<&Worker> {
+        self.worker.as_deref()
+    }
+}
+
+impl Diagnostics {
+    pub fn found(&self, devices: Vec<DeviceReport>) {
+        *self.devices.lock().unwrap_or_else(PoisonError::into_inner) = devices;
+    }
+
+    pub fn created(&self, output: OutputReport) {
+        *self.output.lock().unwrap_or_else(PoisonError::into_inner) = Some(output);
+    }
+
+    /// Marks every running worker as stopped by the watchdog.
+    pub fn abandon(&self, stalled: &str) {
+        *self.stalled.lock().unwrap_or_else(PoisonError::into_inner) = Some(stalled.to_owned());
+        for device in self.devices() {
+            if let Some(worker) = device.worker() {
+                if !matches!(
+                    worker.state(),
+                    WorkerState::Stopped | WorkerState::Unplugged
+                ) {
+                    worker.set(WorkerState::Abandoned);
+                }
+            }
+        }
+    }
+
+    pub fn devices(&self) -> Vec<DeviceReport> {
+        self.devices
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn output(&self) -> Option<OutputReport> {
+        self.output
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn stalled(&self) -> Option<String> {
+        self.stalled
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Everything above as plain text for a bug report, with `key_name`
+    /// spelling out the virtual keyboard's codes.
+    pub fn report(&self, key_name: impl Fn(u16) -> String) -> String {
+        let mut text = format!(
+            "nocaps {} on {}\n",
+            env!("CARGO_PKG_VERSION"),
+            std::env::consts::OS
+        );
+        let or_dash = |text: &str| {
+            if text.is_empty() {
+                "-".to_owned()
+            } else {
+                text.to_owned()
+            }
+        };
+        let devices = self.devices();
+        let _ = writeln!(text, "\ninput devices ({}):", devices.len());
+        for device in &devices {
+            let _ = writeln!(
+                text,
+                "  {}  {}  {}",
+                device.path,
+                or_dash(&device.name),
+                or_dash(&device.id)
+            );
+            let verdict = if device.included {
+                "included"
+            } else {
+                "skipped"
+            };
+            let _ = writeln!(text, "    {verdict}: {}", device.reason);
+            if let Some(worker) = device.worker() {
+                let _ = write!(text, "    worker: {}", worker_state_name(worker.state()));
+                if let Some(error) = worker.error() {
+                    let _ = write!(text, " ({error})");
+                }
+                text.push('\n');
+            }
+        }
+        if let Some(stalled) = self.stalled() {
+            let _ = writeln!(
+                text,
+                "  watchdog released every keyboard: {stalled} stalled"
+            );
+        }
+
+        match self.output() {
+            Some(output) => {
+                let _ = writeln!(
+                    text,
+                    "\nvirtual keyboard: {}  {}",
+                    output.name,
+                    output.path.as_deref().unwrap_or("-")
+                );
+                let keys: Vec<String> = output.keys.iter().map(|&code| key_name(code)).collect();
+                let _ = writeln!(text, "  EV_KEY ({}): {}", keys.len(), keys.join(" "));
+            }
+            None => text.push_str("\nvirtual keyboard: not created\n"),
+        }
+        text
+    }
+}
+
+/// The English name used in reports.
+fn worker_state_name(state: WorkerState) -> &'static str {
+    match state {
+        WorkerState::Starting => "starting",
+        WorkerState::Grabbed => "grabbed",
+        WorkerState::Released => "released",
+        WorkerState::Stopped => "stopped",
+        WorkerState::Abandoned => "abandoned",
+        WorkerState::Unplugged => "unplugged",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_report_lists_devices_workers_and_the_virtual_keyboard() {
+        let diagnostics = Diagnostics::default();
+        let mut keyboard = DeviceReport::new(
+            "/dev/input/event3".to_owned(),
+            "AT Translated Set 2 keyboard".to_owned(),
+            "i8042 0001:0001".to_owned(),
+            true,
+            "has KEY_CAPSLOCK KEY_A KEY_Z KEY_ENTER".to_owned(),
+        );
+        let worker = keyboard.watch();
+        let unreadable = DeviceReport::new(
+            "/dev/input/event7".to_owned(),
+            String::new(),
+            String::new(),
+            false,
+            "cannot open: Permission denied (os error 13)".to_owned(),
+        );
+        diagnostics.found(vec![keyboard, unreadable]);
+        assert_eq!(
+            diagnostics.devices()[0].worker().unwrap().state(),
+            WorkerState::Starting
+        );
+
+        worker.set(WorkerState::Grabbed);
+        let report = diagnostics.report(|code| format!("KEY_{code}"));
+        assert!(report.contains(
+            "  /dev/input/event3  AT Translated Set 2 keyboard  i8042 0001:0001\n    \
+             included: has KEY_CAPSLOCK KEY_A KEY_Z KEY_ENTER\n    worker: grabbed\n"
+        ));
+        assert!(report.contains(
+            "  /dev/input/event7  -  -\n    skipped: cannot open: Permission denied (os error 13)\n\n"
+        ));
+        assert!(report.ends_with("\nvirtual keyboard: not created\n"));
+
+        worker.stop("No such device (os error 19)".to_owned());
+        diagnostics.created(OutputReport {
+            name: "nocaps virtual keyboard".to_owned(),
+            path: Some("/dev/input/event21".to_owned()),
+            keys: vec![1, 58],
+        });
+        diagnostics.abandon("the virtual keyboard");
+        let report = diagnostics.report(|code| format!("KEY_{code}"));
+        assert!(report.contains("    worker: stopped (No such device (os error 19))\n"));
+        assert!(
+            report.contains("  watchdog released every keyboard: the virtual keyboard stalled\n")
+        );
+        assert!(report.ends_with(
+            "\nvirtual keyboard: nocaps virtual keyboard  /dev/input/event21\n  EV_KEY (2): KEY_1 KEY_58\n"
+        ));
+    }
+}
@@ -0,0 +1,65 @@
+use anyhow::{Context, Result};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::thread::{self, Thread};
+
+/// Carries `RuntimeEvent`s from the input threads to a thread of the
+/// observer's own. Observers raise flags, which is an atomic OR and an unpark:
+/// no lock and no allocation on the hot path. Flags raised again before the
+/// thread collects them are collected once.
+pub struct Handoff {
+    raised: Arc<AtomicU32>,
+    receiver: Thread,
+}
+
+impl Handoff {
+    /// Starts the thread `name`, which runs `receive` with the flags raised
+    /// since its last run. The thread lives as long as the process.
+    pub fn spawn(name: &str, mut receive: impl FnMut(u32) + Send + 'static) -> Result<Self> {
+        let raised = Arc::new(AtomicU32::new(0));
+        let collected = Arc::clone(&raised);
+        let receiver = thread::Builder::new()
+            .name(name.to_owned())
+            .spawn(move || loop {
+                // An unpark before the park makes it return at once, so no
+                // flag raised between the swap and the park is missed.
+                thread::park();
+                let flags = collected.swap(0, Ordering::AcqRel);
+                if flags != 0 {
+                    receive(flags);
+                }
+            })
+            .with_context(|| format!("start {name} thread"))?;
+        Ok(Self {
+            raised,
+            receiver: receiver.thread().clone(),
+        })
+    }
+
+    pub fn raise(&self, flags: u32) {
+        self.raised.fetch_or(flags, Ordering::AcqRel);
+        self.receiver.unpark();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    #[test]
+    fn raised_flags_reach_the_thread() {
+        let (collected, received) = mpsc::channel();
+        let handoff = Handoff::spawn("nocaps-handoff-test", move |flags| {
+            let _ = collected.send(flags);
+        })
+        .unwrap();
+        handoff.raise(0b01);
+        let mut flags = received.recv_timeout(Duration::from_secs(5)).unwrap();
+        handoff.raise(0b10);
+        while flags != 0b11 {
+            flags |= received.recv_timeout(Duration::from_secs(5)).unwrap();
+        }
+    }
+}
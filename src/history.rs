@@ -0,0 +1,288 @@
+use crate::config::{Action, Bindings, Config, Language, Theme};
+use crate::locale::Message;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// How many changes the window can take back.
+const LIMIT: usize = 50;
+
+/// The parts of the configuration the window edits: which profile is active,
+/// the profiles a change touched, and the window's theme and language.
+/// Switching remapping or statistics on and off is left out, so undo never
+/// turns remapping back on.
+#[derive(Clone, Debug, PartialEq)]
+struct Snapshot {
+    profile: String,
+    // Only the profiles the change created, removed or edited; `None` where
+    // the profile did not exist.
+    profiles: BTreeMap<String, Option<Bindings>>,
+    theme: Theme,
+    language: Language,
+}
+
+impl Snapshot {
+    fn of(config: &Config, touched: &[&String]) -> Self {
+        Self {
+            profile: config.profile.clone(),
+            profiles: touched
+                .iter()
+                .map(|name| ((*name).clone(), config.profiles.get(*name).cloned()))
+                .collect(),
+            theme: config.theme,
+            language: config.language,
+        }
+    }
+}
+
+/// One change made in the window: the edited parts before and after.
+pub struct Step {
+    before: Snapshot,
+    after: Snapshot,
+    pub message: Message,
+}
+
+impl Step {
+    /// `config` with this change taken back, or with `redo` made again. Only
+    /// what the change touched is set, so edits made since then elsewhere,
+    /// such as from the control socket, are kept.
+    pub fn apply(&self, config: &mut Config, redo: bool) {
+        let (from, to) = if redo {
+            (&self.before, &self.after)
+        } else {
+            (&self.after, &self.before)
+        };
+        if from.theme != to.theme {
+            config.theme = to.theme;
+        }
+        if from.language != to.language {
+            config.language = to.language;
+        }
+        for (name, wanted) in &to.profiles {
+            match (&from.profiles[name], wanted) {
+                (_, None) => {
+                    config.profiles.remove(name);
+                }
+                (None, Some(bindings)) => {
+                    config.profiles.insert(name.clone(), bindings.clone());
+                }
+                (Some(from), Some(to)) => {
+                    rebind(config.profiles.entry(name.clone()).or_default(), from, to)
+                }
+            }
+        }
+        if from.profile != to.profile {
+            config.profile = to.profile.clone();
+        }
+    }
+}
+
+/// Sets the actions whose keys differ between `from` and `to` to their keys
+/// in `to`, leaving every other binding in `bindings` as it is.
+fn rebind(bindings: &mut Bindings, from: &Bindings, to: &Bindings) {
+    let changed: Vec<Action> = from
+        .keys()
+        .chain(to.keys())
+        .filter(|action| from.get(action) != to.get(action))
+        .copied()
+        .collect();
+    for action in &changed {
+        bindings.remove(action);
+    }
+    for action in changed {
+        if let Some(key) = to.get(&action) {
+            // A key is bound to one action at a time, as in `Config::bind`.
+            bindings.retain(|_, current| current != key);
+            bindings.insert(action, *key);
+        }
+    }
+}
+
+/// Undo and redo for the configuration window. The window applies the
+/// steps itself; this only keeps them in order.
+#[derive(Default)]
+pub struct History {
+    // Oldest first.
+    undo: Vec<Step>,
+    // Most recently undone last.
+    redo: Vec<Step>,
+}
+
+impl History {
+    /// Remembers a change that was just applied, given the configuration in
+    /// effect before and after it. Anything undone before it can no longer be
+    /// redone.
+    pub fn record(&mut self, before: &Config, after: &Config, message: Message) {
+        let touched: Vec<&String> = before
+            .profiles
+            .keys()
+            .chain(after.profiles.keys())
+            .filter(|name| before.profiles.get(*name) != after.profiles.get(*name))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let (before, after) = (
+            Snapshot::of(before, &touched),
+            Snapshot::of(after, &touched),
+        );
+        if before == after {
+            return;
+        }
+        self.redo.clear();
+        if self.undo.len() == LIMIT {
+            self.undo.remove(0);
+        }
+        self.undo.push(Step {
+            before,
+            after,
+            message,
+        });
+    }
+
+    /// The change the next undo reverts.
+    pub fn undoable(&self) -> Option<&Step> {
+        self.undo.last()
+    }
+
+    /// The change the next redo applies again.
+    pub fn redoable(&self) -> Option<&Step> {
+        self.redo.last()
+    }
+
+    /// Call once `undoable` has been reverted.
+    pub fn undone(&mut self) {
+        if let Some(step) = self.undo.pop() {
+            self.redo.push(step);
+        }
+    }
+
+    /// Call once `redoable` has been applied again.
+    pub fn redone(&mut self) {
+        if let Some(step) = self.redo.pop() {
+            self.undo.push(step);
+        }
+    }
+
+    /// Changes that can be undone, newest first.
+    pub fn done(&self) -> impl Iterator<Item = &Step> {
+        self.undo.iter().rev()
+    }
+
+    /// Changes that can be redone, next first.
+    pub fn undone_steps(&self) -> impl Iterator<Item = &Step> {
+        self.redo.iter().rev()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::InputKey;
+
+    fn change(text: &str) -> Message {
+        Message::new("status.undone").with("change", text)
+    }
+
+    #[test]
+    fn undo_and_redo_walk_the_changes_in_order() {
+        let start = Config::default();
+        let mut first = start.clone();
+        first.bind(Action::Home, InputKey::Q);
+        let mut second = first.clone();
+        second.theme = Theme::Amber;
+
+        let mut history = History::default();
+        history.record(&start, &first, change("home"));
+        let mut disabled = first.clone();
+        disabled.enabled = false;
+        history.record(&first, &disabled, change("nothing the window undoes"));
+        history.record(&first, &second, change("amber"));
+        assert_eq!(history.done().count(), 2);
+
+        let mut config = second.clone();
+        history.undoable().unwrap().apply(&mut config, false);
+        history.undone();
+        assert_eq!(config, first);
+        assert_eq!(history.undoable().unwrap().message, change("home"));
+        assert_eq!(history.redoable().unwrap().message, change("amber"));
+
+        history.record(&first, &start, change("back"));
+        assert!(history.redoable().is_none());
+        assert_eq!(history.done().next().unwrap().message, change("back"));
+
+        for _ in 0..LIMIT + 5 {
+            history.record(&start, &first, change("again"));
+        }
+        assert_eq!(history.done().count(), LIMIT);
+    }
+
+    #[test]
+    fn undo_keeps_changes_made_outside_the_window() {
+        let start = Config::default();
+        let mut moved = start.clone();
+        // Moving Q to Home takes it from volume down.
+        moved.bind(Action::Home, InputKey::Q);
+        let mut history = History::default();
+        history.record(&start, &moved, change("home"));
+
+        // Meanwhile the control socket disabled remapping and bound End.
+        let mut current = moved.clone();
+        current.enabled = false;
+        current.bind(Action::End, InputKey::Y);
+        history.undoable().unwrap().apply(&mut current, false);
+
+        assert_eq!(current.key_for(Action::VolumeDown), Some(InputKey::Q));
+        assert_eq!(current.key_for(Action::Home), Some(InputKey::H));
+        assert_eq!(current.key_for(Action::End), Some(InputKey::Y));
+        assert!(!current.enabled);
+    }
+
+    #[test]
+    fn profile_operations_are_undone_and_redone_whole() {
+        let mut start = Config::default();
+        start.duplicate_profile("work").unwrap();
+        start.bind(Action::Home, InputKey::Q);
+        start.switch_profile("default").unwrap();
+        type Operation = fn(&mut Config);
+        let operations: [(&str, Operation); 4] = [
+            ("rename", |config| {
+                config.rename_profile("Coding Setup").unwrap()
+            }),
+            ("create", |config| config.create_profile("empty").unwrap()),
+            ("duplicate", |config| {
+                config.duplicate_profile("copy").unwrap()
+            }),
+            ("delete", |config| config.delete_profile().unwrap()),
+        ];
+        for (name, operation) in operations {
+            let mut after = start.clone();
+            operation(&mut after);
+            let mut history = History::default();
+            history.record(&start, &after, change(name));
+
+            let mut config = after.clone();
+            history.undoable().unwrap().apply(&mut config, false);
+            assert_eq!(config, start, "undo {name}");
+            history.undone();
+            history.redoable().unwrap().apply(&mut config, true);
+            assert_eq!(config, after, "redo {name}");
+        }
+    }
+
+    #[test]
+    fn undoing_a_profile_switch_keeps_later_edits() {
+        let mut start = Config::default();
+        start.duplicate_profile("work").unwrap();
+        start.switch_profile("default").unwrap();
+        let mut switched = start.clone();
+        switched.switch_profile("work").unwrap();
+        let mut history = History::default();
+        history.record(&start, &switched, change("work"));
+
+        // Bound in the work profile after switching to it.
+        let mut current = switched.clone();
+        current.bind(Action::End, InputKey::Y);
+        history.undoable().unwrap().apply(&mut current, false);
+
+        assert_eq!(current.profile, "default");
+        assert_eq!(current.profiles["work"][&Action::End], InputKey::Y);
+    }
+}
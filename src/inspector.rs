@@ -0,0 +1,217 @@
+use crate::config::InputKey;
+use std::sync::atomic::{fence, AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+
+/// How many of the latest key events the inspector keeps.
+const CAPACITY: usize = 256;
+/// Stamped on a slot while a worker overwrites it.
+const WRITING: u64 = u64::MAX;
+
+/// What nocaps did with an inspected key event.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Output {
+    /// Written to the virtual keyboard with this code.
+    Emitted(u16),
+    /// Kept from applications: Caps Lock, the toggle key or the emergency chord.
+    Swallowed,
+    /// The keyboard was not grabbed, so the event reached applications directly.
+    Ungrabbed,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct InspectedEvent {
+    pub device: String,
+    pub code: u16,
+    /// 1 for a press, 2 for auto-repeat and 0 for a release.
+    pub value: i32,
+    pub key: Option<InputKey>,
+    pub layer: bool,
+    pub output: Output,
+}
+
+/// Recent key events as the Linux workers saw and translated them, for the
+/// debug panel. Off until the panel switches it on; while off, workers pay one
+/// relaxed load per event. Workers write into a fixed ring without locking,
+/// each slot guarded by a sequence stamp, and the window reads whatever is
+/// complete. Nothing is written to disk.
+pub struct Inspector {
+    enabled: AtomicBool,
+    written: AtomicU64,
+    slots: [Slot; CAPACITY],
+    // Only touched when a worker starts and when the window reads.
+    devices: Mutex<Vec<String>>,
+}
+
+#[derive(Default)]
+struct Slot {
+    // The sequence number of the event in `packed`, zero while empty.
+    stamp: AtomicU64,
+    packed: AtomicU64,
+}
+
+impl Default for Inspector {
+    fn default() -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            written: AtomicU64::new(0),
+            slots: std::array::from_fn(|_| Slot::default()),
+            devices: Mutex::new(Vec::new()),
+        }
+    }
+}
+
+impl Inspector {
+    /// Gives a worker the number it tags its events with.
+    pub fn register(&self, device: &str) -> u8 {
+        let mut devices = self.devices.lock().unwrap_or_else(PoisonError::into_inner);
+        devices.push(device.to_owned());
+        u8::try_from(devices.len() - 1).unwrap_or(u8::MAX)
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Turning the inspector off also forgets what it saw.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+        if !enabled {
+            self.clear();
+        }
+    }
+
+    pub fn clear(&self) {
+        for slot in &self.slots {
+            slot.stamp.store(0, Ordering::Release);
+        }
+    }
+
+    pub fn record(
+        &self,
+        device: u8,
+        code: u16,
+        value: i32,
+        key: Option<InputKey>,
+        layer: bool,
+        output: Output,
+    ) {
+        if !self.is_enabled() {
+            return;
+        }
+        let (kind, emitted) = match output {
+            Output::Emitted(code) => (0, code),
+            Output::Swallowed => (1, 0),
+            Output::Ungrabbed => (2, 0),
+        };
+        let packed = u64::from(code)
+            | (value.clamp(0, 2) as u64) << 16
+            | (key.map_or(0, |key| key.index() as u64 + 1)) << 18
+            | u64::from(layer) << 26
+            | kind << 27
+            | u64::from(emitted) << 29
+            | u64::from(device) << 45;
+
+        let sequence = self.written.fetch_add(1, Ordering::Relaxed) + 1;
+        let slot = &self.slots[sequence as usize % CAPACITY];
+        slot.stamp.store(WRITING, Ordering::Relaxed);
+        fence(Ordering::Release);
+        slot.packed.store(packed, Ordering::Relaxed);
+        slot.stamp.store(sequence, Ordering::Release);
+    }
+
+    /// The events still in the ring, newest first. Slots being rewritten at
+    /// that moment are skipped.
+    pub fn recent(&self) -> Vec<InspectedEvent> {
+        let devices = self
+            .devices
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        let written = self.written.load(Ordering::Acquire);
+        let oldest = written.saturating_sub(CAPACITY as u64) + 1;
+        (oldest..=written)
+            .rev()
+            .filter_map(|sequence| {
+                let slot = &self.slots[sequence as usize % CAPACITY];
+                let stamp = slot.stamp.load(Ordering::Acquire);
+                let packed = slot.packed.load(Ordering::Relaxed);
+                fence(Ordering::Acquire);
+                (stamp == sequence && slot.stamp.load(Ordering::Relaxed) == stamp)
+                    .then(|| unpack(packed, &devices))
+            })
+            .collect()
+    }
+}
+
+fn unpack(packed: u64, devices: &[String]) -> InspectedEvent {
+    let field = |shift: u32, bits: u32| (packed >> shift) & ((1 << bits) - 1);
+    let emitted = field(29, 16) as u16;
+    InspectedEvent {
+        device: devices
+            .get(field(45, 8) as usize)
+            .cloned()
+            .unwrap_or_default(),
+        code: field(0, 16) as u16,
+        value: field(16, 2) as i32,
+        key: (field(18, 8) as usize)
+            .checked_sub(1)
+            .and_then(|index| InputKey::ALL.get(index))
+            .copied(),
+        layer: field(26, 1) == 1,
+        output: match field(27, 2) {
+            0 => Output::Emitted(emitted),
+            1 => Output::Swallowed,
+            _ => Output::Ungrabbed,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_latest_events_only_while_enabled() {
+        let inspector = Inspector::default();
+        let keyboard = inspector.register("AT keyboard");
+        inspector.record(
+            keyboard,
+            23,
+            1,
+            Some(InputKey::I),
+            true,
+            Output::Emitted(103),
+        );
+        assert!(inspector.recent().is_empty());
+
+        inspector.set_enabled(true);
+        for code in 0..CAPACITY as u16 + 10 {
+            inspector.record(keyboard, code, 1, None, false, Output::Swallowed);
+        }
+        inspector.record(
+            keyboard,
+            23,
+            2,
+            Some(InputKey::I),
+            true,
+            Output::Emitted(103),
+        );
+        let recent = inspector.recent();
+        assert_eq!(recent.len(), CAPACITY);
+        assert_eq!(
+            recent[0],
+            InspectedEvent {
+                device: "AT keyboard".to_owned(),
+                code: 23,
+                value: 2,
+                key: Some(InputKey::I),
+                layer: true,
+                output: Output::Emitted(103),
+            }
+        );
+        assert_eq!(recent[1].code, CAPACITY as u16 + 9);
+
+        inspector.set_enabled(false);
+        assert!(inspector.recent().is_empty());
+    }
+}
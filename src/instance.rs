@@ -0,0 +1,33 @@
+use crate::config::ConfigStore;
+use anyhow::{Context, Result};
+use std::fs::{self, File, TryLockError};
+
+const LOCK_NAME: &str = "nocaps.lock";
+
+/// Proof that this process is the only running nocaps for the user. The OS drops
+/// the lock when the process exits, including after a crash, so no stale state
+/// can keep a later launch out.
+pub struct InstanceLock {
+    _file: File,
+}
+
+/// Takes the per-user instance lock, or returns `None` when another nocaps holds it.
+pub fn claim(store: &ConfigStore) -> Result<Option<InstanceLock>> {
+    let directory = store.runtime_dir()?;
+    fs::create_dir_all(&directory)
+        .with_context(|| format!("create runtime directory {}", directory.display()))?;
+    let path = directory.join(LOCK_NAME);
+    let file = File::options()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&path)
+        .with_context(|| format!("open instance lock {}", path.display()))?;
+    match file.try_lock() {
+        Ok(()) => Ok(Some(InstanceLock { _file: file })),
+        Err(TryLockError::WouldBlock) => Ok(None),
+        Err(TryLockError::Error(error)) => {
+            Err(error).with_context(|| format!("lock {}", path.display()))
+        }
+    }
+}
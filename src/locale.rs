@@ -0,0 +1,371 @@
+use crate::config::{
+    Action, Category, InputKey, Language, ProfileError, Theme, PROFILE_NAME_LIMIT,
+};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+/// The bundled catalogs. English is the template: every other catalog
+/// translates its messages, and anything a catalog leaves out is shown in
+/// English.
+const CATALOGS: &[(Language, &str)] = &[
+    (Language::English, include_str!("../assets/locales/en.json")),
+    (Language::German, include_str!("../assets/locales/de.json")),
+    (
+        Language::Japanese,
+        include_str!("../assets/locales/ja.json"),
+    ),
+    (
+        Language::SimplifiedChinese,
+        include_str!("../assets/locales/zh-Hans.json"),
+    ),
+];
+
+/// One file in `assets/locales`. Categories, actions and keys are named as in
+/// `config.json`; messages by the ids the window uses.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Catalog {
+    /// The language's own name for itself.
+    name: String,
+    /// Set for scripts the bundled font does not cover.
+    cjk: bool,
+    categories: BTreeMap<Category, String>,
+    actions: BTreeMap<Action, String>,
+    keys: BTreeMap<InputKey, String>,
+    messages: BTreeMap<String, String>,
+}
+
+impl Catalog {
+    fn parse(language: Language) -> Self {
+        let (_, json) = CATALOGS
+            .iter()
+            .find(|(bundled, _)| *bundled == language)
+            .expect("every language but System has a catalog");
+        serde_json::from_str(json).expect("assets/locales must contain valid catalogs")
+    }
+}
+
+/// The window's text in one language.
+pub struct Locale {
+    catalog: Catalog,
+}
+
+/// The locale for `language`, with `System` resolved from the environment.
+/// Each is parsed once and kept for the life of the process.
+pub fn locale(language: Language) -> &'static Locale {
+    static LOADED: [OnceLock<Locale>; CATALOGS.len()] = [const { OnceLock::new() }; CATALOGS.len()];
+    let language = match language {
+        Language::System => system_language(),
+        language => language,
+    };
+    let index = CATALOGS
+        .iter()
+        .position(|(bundled, _)| *bundled == language)
+        .unwrap_or(0);
+    LOADED[index].get_or_init(|| Locale {
+        catalog: Catalog::parse(language),
+    })
+}
+
+fn english() -> &'static Locale {
+    locale(Language::English)
+}
+
+/// The first of the usual locale variables that is set, reduced to a language
+/// nocaps has a catalog for. Anything else, or nothing set, means English.
+fn system_language() -> Language {
+    let value = ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default();
+    language_from_locale(&value)
+}
+
+/// Chinese only counts where Simplified Chinese is written: mainland China,
+/// Singapore, or the script named outright. Traditional Chinese locales such
+/// as `zh_TW` and `zh_HK` get English rather than the wrong script.
+fn language_from_locale(value: &str) -> Language {
+    let mut parts = value.split(['_', '-', '.', '@']);
+    match (parts.next(), parts.next()) {
+        (Some("de"), _) => Language::German,
+        (Some("ja"), _) => Language::Japanese,
+        (Some("zh"), Some("CN" | "SG" | "Hans")) => Language::SimplifiedChinese,
+        _ => Language::English,
+    }
+}
+
+impl Locale {
+    /// Whether the window needs a system CJK font next to the bundled one.
+    pub fn needs_cjk_font(&self) -> bool {
+        self.catalog.cjk
+    }
+
+    /// The message `id`, or its id when even English lacks it.
+    pub fn text<'a>(&'a self, id: &'a str) -> &'a str {
+        self.catalog
+            .messages
+            .get(id)
+            .or_else(|| english().catalog.messages.get(id))
+            .map_or(id, String::as_str)
+    }
+
+    /// The message `id` with each `{name}` replaced by its value.
+    pub fn fill(&self, id: &str, values: &[(&str, &str)]) -> String {
+        values
+            .iter()
+            .fold(self.text(id).to_owned(), |text, (name, value)| {
+                text.replace(&format!("{{{name}}}"), value)
+            })
+    }
+
+    pub fn action(&self, action: Action) -> &str {
+        self.catalog
+            .actions
+            .get(&action)
+            .map_or(action.label(), String::as_str)
+    }
+
+    pub fn category(&self, category: Category) -> &str {
+        self.catalog
+            .categories
+            .get(&category)
+            .map_or(category.label(), String::as_str)
+    }
+
+    pub fn key(&self, key: InputKey) -> &str {
+        self.catalog
+            .keys
+            .get(&key)
+            .map_or(key.label(), String::as_str)
+    }
+
+    pub fn theme(&self, theme: Theme) -> &str {
+        self.text(match theme {
+            Theme::CrtGreen => "theme.crt_green",
+            Theme::Amber => "theme.amber",
+            Theme::Light => "theme.light",
+            Theme::HighContrast => "theme.high_contrast",
+        })
+    }
+
+    /// A language as listed in the language menu: by its own name, so it can be
+    /// found whatever the current language is.
+    pub fn language_name(&self, language: Language) -> String {
+        match language {
+            Language::System => self.fill(
+                "language.system",
+                &[("language", &locale(language).catalog.name)],
+            ),
+            language => locale(language).catalog.name.clone(),
+        }
+    }
+}
+
+/// A message kept to be shown again later, such as a change in the history
+/// menu. It holds the id and the placeholder values rather than the text, so
+/// it reads in whatever language the window has switched to by then.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Message {
+    id: &'static str,
+    values: Vec<(&'static str, Value)>,
+}
+
+/// A placeholder value. Actions, keys, themes and languages are named when
+/// the message is shown; anything else is shown as it was given.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Action(Action),
+    Key(InputKey),
+    Theme(Theme),
+    Language(Language),
+    Text(String),
+}
+
+impl Message {
+    pub fn new(id: &'static str) -> Self {
+        Self {
+            id,
+            values: Vec::new(),
+        }
+    }
+
+    /// Fills the placeholder `{name}` with `value`.
+    pub fn with(mut self, name: &'static str, value: impl Into<Value>) -> Self {
+        self.values.push((name, value.into()));
+        self
+    }
+
+    pub fn render(&self, locale: &Locale) -> String {
+        let values: Vec<(&str, String)> = self
+            .values
+            .iter()
+            .map(|(name, value)| {
+                let value = match value {
+                    Value::Action(action) => locale.action(*action).to_owned(),
+                    Value::Key(key) => locale.key(*key).to_owned(),
+                    Value::Theme(theme) => locale.theme(*theme).to_owned(),
+                    Value::Language(language) => locale.language_name(*language),
+                    Value::Text(text) => text.clone(),
+                };
+                (*name, value)
+            })
+            .collect();
+        let values: Vec<(&str, &str)> = values
+            .iter()
+            .map(|(name, value)| (*name, value.as_str()))
+            .collect();
+        locale.fill(self.id, &values)
+    }
+}
+
+impl From<&ProfileError> for Message {
+    fn from(error: &ProfileError) -> Self {
+        match error {
+            ProfileError::Missing(name) => {
+                Message::new("profile.error_missing").with("profile", name.as_str())
+            }
+            ProfileError::LastProfile => Message::new("profile.error_last"),
+            ProfileError::Unnamed => Message::new("profile.error_unnamed"),
+            ProfileError::TooLong => Message::new("profile.error_too_long")
+                .with("limit", PROFILE_NAME_LIMIT.to_string().as_str()),
+            ProfileError::Taken(name) => {
+                Message::new("profile.error_taken").with("profile", name.as_str())
+            }
+        }
+    }
+}
+
+impl From<Action> for Value {
+    fn from(action: Action) -> Self {
+        Self::Action(action)
+    }
+}
+
+impl From<InputKey> for Value {
+    fn from(key: InputKey) -> Self {
+        Self::Key(key)
+    }
+}
+
+impl From<Theme> for Value {
+    fn from(theme: Theme) -> Self {
+        Self::Theme(theme)
+    }
+}
+
+impl From<Language> for Value {
+    fn from(language: Language) -> Self {
+        Self::Language(language)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(text: &str) -> Self {
+        Self::Text(text.to_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_catalog_translates_every_message_with_the_same_placeholders() {
+        let placeholders = |text: &str| {
+            let mut names: Vec<String> = text
+                .split('{')
+                .skip(1)
+                .filter_map(|rest| rest.split_once('}').map(|(name, _)| name.to_owned()))
+                .collect();
+            names.sort();
+            names
+        };
+        let english = Catalog::parse(Language::English);
+        for (language, _) in CATALOGS {
+            let catalog = Catalog::parse(*language);
+            assert!(!catalog.name.is_empty(), "{language:?}");
+            assert_eq!(
+                catalog.messages.keys().collect::<Vec<_>>(),
+                english.messages.keys().collect::<Vec<_>>(),
+                "{language:?}"
+            );
+            for (id, text) in &catalog.messages {
+                assert_eq!(
+                    placeholders(text),
+                    placeholders(&english.messages[id]),
+                    "{language:?} {id}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn missing_translations_fall_back_to_english() {
+        let german = locale(Language::German);
+        assert_eq!(german.action(Action::LeftControl), "Strg");
+        assert_eq!(german.key(InputKey::Q), "Q");
+        assert_eq!(german.category(Category::Media), "Medien");
+        assert_eq!(
+            german.fill("status.bound", &[("action", "Strg"), ("key", "A")]),
+            "Strg ist jetzt Caps Lock + A"
+        );
+        assert_eq!(german.text("no.such.message"), "no.such.message");
+        assert_eq!(locale(Language::Japanese).action(Action::Home), "Home");
+        assert!(locale(Language::Japanese).needs_cjk_font());
+    }
+
+    #[test]
+    fn messages_are_rendered_in_the_language_shown_now() {
+        let message = Message::new("status.bound")
+            .with("action", Action::Home)
+            .with("key", InputKey::H);
+        assert_eq!(
+            message.render(locale(Language::English)),
+            "Home is now Caps Lock + H"
+        );
+        assert_eq!(
+            message.render(locale(Language::German)),
+            "Pos1 ist jetzt Caps Lock + H"
+        );
+    }
+
+    #[test]
+    fn profile_errors_read_as_their_english_text() {
+        for error in [
+            ProfileError::Missing("work".to_owned()),
+            ProfileError::LastProfile,
+            ProfileError::Unnamed,
+            ProfileError::TooLong,
+            ProfileError::Taken("work".to_owned()),
+        ] {
+            assert_eq!(
+                Message::from(&error).render(locale(Language::English)),
+                error.to_string()
+            );
+        }
+    }
+
+    #[test]
+    fn system_locales_map_to_bundled_languages() {
+        assert_eq!(language_from_locale("de_AT.UTF-8"), Language::German);
+        assert_eq!(language_from_locale("ja_JP.UTF-8"), Language::Japanese);
+        assert_eq!(
+            language_from_locale("zh_CN.UTF-8"),
+            Language::SimplifiedChinese
+        );
+        for simplified in ["zh_SG.UTF-8", "zh-Hans", "zh-Hans-HK"] {
+            assert_eq!(
+                language_from_locale(simplified),
+                Language::SimplifiedChinese
+            );
+        }
+        for traditional in ["zh_TW.UTF-8", "zh_HK.UTF-8", "zh-Hant", "zh"] {
+            assert_eq!(language_from_locale(traditional), Language::English);
+        }
+        assert_eq!(language_from_locale("fr_FR.UTF-8"), Language::English);
+        assert_eq!(language_from_locale("C"), Language::English);
+        assert_eq!(language_from_locale(""), Language::English);
+    }
+}
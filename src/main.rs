@@ -1,38 +1,93 @@
 #![cfg_attr(target_os = "windows", windows_subsystem = "windows")]
 
-use anyhow::Result;
-use std::sync::atomic::AtomicBool;
-use std::sync::Arc;
+use anyhow::{Context, Result};
+use std::process::ExitCode;
+use std::sync::{mpsc, Arc};
 
+#[cfg(feature = "gui")]
 mod app;
+mod cli;
 mod config;
+#[cfg(unix)]
+mod control;
+mod controller;
+#[cfg(target_os = "linux")]
+mod dbus;
+#[cfg(target_os = "linux")]
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+mod diagnostics;
+mod handoff;
+#[cfg(feature = "gui")]
+mod history;
+#[cfg(target_os = "linux")]
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+mod inspector;
+mod instance;
+#[cfg(feature = "gui")]
+mod locale;
+mod migration;
 mod platform;
+#[cfg(target_os = "linux")]
+mod service;
+mod usage;
+mod watch;
 
+use cli::Command;
 use config::{Config, ConfigStore, RuntimeBindings};
+use controller::{Controller, Notice};
+use usage::{UsageRecorder, UsageStore};
+use watch::ConfigWatcher;
 
-fn main() -> Result<()> {
+fn main() -> Result<ExitCode> {
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("nocaps=info"))
         .init();
 
-    #[cfg(target_os = "linux")]
-    gtk::init()
-        .map_err(|error| anyhow::anyhow!("initialize GTK for the Linux tray icon: {error}"))?;
+    match Command::parse(std::env::args().skip(1)) {
+        Ok(Command::Run) => run(!cfg!(feature = "gui")).map(|()| ExitCode::SUCCESS),
+        Ok(Command::Headless) => run(true).map(|()| ExitCode::SUCCESS),
+        Ok(command) => Ok(cli::execute(command)),
+        Err(error) => Ok(cli::usage_error(&error)),
+    }
+}
 
+/// Remaps until the user quits from the tray or window, or, when `headless`,
+/// until a termination signal arrives.
+fn run(headless: bool) -> Result<()> {
     let store = ConfigStore::discover()?;
+    // Two instances would fight over the keyboard, so a second launch only asks
+    // the first to show its window. The lock is held until this process exits.
+    let Some(_instance) = instance::claim(&store)? else {
+        log::info!("nocaps is already running");
+        #[cfg(unix)]
+        if let Err(error) = control::request(&store, "show") {
+            log::warn!("could not ask the running nocaps to show its window: {error:#}");
+        }
+        return Ok(());
+    };
+
+    #[cfg(all(feature = "gui", target_os = "linux"))]
+    if !headless {
+        gtk::init()
+            .map_err(|error| anyhow::anyhow!("initialize GTK for the Linux tray icon: {error}"))?;
+    }
+
     let mut startup_errors = Vec::new();
     let config = match store.load_or_create() {
         Ok(config) => config,
         Err(error) => {
+            log::error!("could not load the configuration: {error:#}");
             startup_errors.push(format!("Could not load the configuration: {error:#}"));
             Config::default()
         }
     };
     let runtime = Arc::new(RuntimeBindings::new(&config)?);
-    let running = Arc::new(AtomicBool::new(true));
 
     // Keep the platform hook alive for the full lifetime of the UI event loop.
     let keyboard = match platform::start_keyboard(runtime.clone()) {
         Ok(keyboard) => Some(keyboard),
+        // Headless, there is no window to explain the failure in and nothing
+        // left to run, so exit with the error instead.
+        Err(error) if headless => return Err(error.context("keyboard remapping is unavailable")),
         Err(error) => {
             log::error!("keyboard remapping is unavailable: {error:#}");
             startup_errors.push(format!("Keyboard remapping is unavailable: {error}"));
@@ -40,8 +95,74 @@ fn main() -> Result<()> {
         }
     };
 
-    let startup_error = (!startup_errors.is_empty()).then(|| startup_errors.join("\n"));
-    let result = app::run(runtime, config, store, running, startup_error);
+    let usage = UsageStore::beside(&store)
+        .and_then(|usage| UsageRecorder::start(Arc::clone(&runtime), usage))
+        .map_err(|error| log::warn!("usage statistics are unavailable: {error:#}"))
+        .ok();
+
+    let controller = Arc::new(Controller::new(Arc::clone(&runtime), store.clone(), config));
+    if let Err(error) = controller.follow_gestures() {
+        log::warn!("keyboard gestures are unavailable: {error:#}");
+    }
+    let watched = Arc::clone(&controller);
+    let watcher = match ConfigWatcher::new(store, move |loaded| {
+        // Failures are announced to the window; the running bindings stay.
+        let _ = watched.adopt(loaded);
+    }) {
+        Ok(watcher) => Some(watcher),
+        Err(error) => {
+            log::warn!("live configuration reload is unavailable: {error:#}");
+            startup_errors.push(format!(
+                "Live reload of config.json is unavailable: {error}"
+            ));
+            None
+        }
+    };
+    #[cfg(unix)]
+    let control = control::ControlServer::start(Arc::clone(&controller))
+        .map_err(|error| log::warn!("control socket is unavailable: {error:#}"))
+        .ok();
+    #[cfg(target_os = "linux")]
+    let dbus = dbus::DbusService::start(Arc::clone(&controller), runtime)
+        .map_err(|error| log::warn!("D-Bus interface is unavailable: {error:#}"))
+        .ok();
+
+    #[cfg(feature = "gui")]
+    let result = if headless {
+        run_headless(&controller)
+    } else {
+        let running = Arc::new(std::sync::atomic::AtomicBool::new(true));
+        let startup_error = (!startup_errors.is_empty()).then(|| startup_errors.join("\n"));
+        app::run(controller, running, startup_error)
+    };
+    #[cfg(not(feature = "gui"))]
+    let result = run_headless(&controller);
+    #[cfg(target_os = "linux")]
+    drop(dbus);
+    #[cfg(unix)]
+    drop(control);
+    drop(watcher);
     drop(keyboard);
+    drop(usage);
     result
 }
+
+/// Logs what the window would show and blocks until Ctrl+C, SIGTERM or SIGHUP.
+fn run_headless(controller: &Controller) -> Result<()> {
+    controller.subscribe(|notice| match notice {
+        Notice::Changed(message) => log::info!("{message}"),
+        Notice::Reloaded => log::info!("config.json reloaded"),
+        Notice::NotReloaded(error) => log::warn!("config.json not applied — {error}"),
+        Notice::Unsaved { change, error } => log::warn!("{change} — not saved: {error}"),
+        Notice::ShowWindow => log::info!("running headless; there is no window to show"),
+    });
+    let (stop, stopped) = mpsc::channel();
+    ctrlc::set_handler(move || {
+        let _ = stop.send(());
+    })
+    .context("install termination handler")?;
+    log::info!("running headless; stop with Ctrl+C or SIGTERM");
+    let _ = stopped.recv();
+    log::info!("shutting down");
+    Ok(())
+}
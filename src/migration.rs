@@ -0,0 +1,190 @@
+use crate::config::{Config, CONFIG_VERSION};
+use anyhow::{anyhow, Context, Result};
+use serde_json::{json, Value};
+
+/// Upgrade steps between on-disk formats. `STEPS[n]` rewrites a version `n + 1`
+/// document into version `n + 2`, so files from any release reach the current
+/// schema by running every step from their own version onwards.
+const STEPS: &[fn(Value) -> Result<Value>] = &[
+    named_profiles,
+    toggle_hotkey,
+    usage_statistics,
+    window_theme,
+    window_language,
+];
+
+const _: () = assert!(STEPS.len() + 1 == CONFIG_VERSION as usize);
+
+pub struct Upgrade {
+    pub config: Config,
+    /// The version the file was written with, when it predates `CONFIG_VERSION`.
+    pub upgraded_from: Option<u32>,
+}
+
+/// Reads a configuration file of any supported version. Current files are decoded
+/// directly so parse errors keep their line and column.
+pub fn upgrade(bytes: &[u8]) -> Result<Upgrade> {
+    let mut document: Value = serde_json::from_slice(bytes)?;
+    let version = document
+        .get("version")
+        .and_then(Value::as_u64)
+        .ok_or_else(|| anyhow!("the configuration has no numeric version"))?;
+    let version = u32::try_from(version)
+        .ok()
+        .filter(|version| (1..=CONFIG_VERSION).contains(version))
+        .ok_or_else(|| {
+            anyhow!(
+                "unsupported configuration version {version}; this nocaps reads versions 1 to {CONFIG_VERSION}"
+            )
+        })?;
+
+    if version == CONFIG_VERSION {
+        return Ok(Upgrade {
+            config: serde_json::from_slice(bytes)?,
+            upgraded_from: None,
+        });
+    }
+
+    for (step, from) in STEPS[version as usize - 1..].iter().zip(version..) {
+        document = step(document).with_context(|| {
+            format!("upgrade configuration from version {from} to {}", from + 1)
+        })?;
+        document["version"] = Value::from(from + 1);
+    }
+    Ok(Upgrade {
+        config: serde_json::from_value(document)?,
+        upgraded_from: Some(version),
+    })
+}
+
+/// Version 2 moved the single `bindings` table into a profile named "default".
+fn named_profiles(mut document: Value) -> Result<Value> {
+    let object = document
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("the configuration is not a JSON object"))?;
+    let bindings = object
+        .remove("bindings")
+        .ok_or_else(|| anyhow!("the configuration has no bindings"))?;
+    object.insert("profile".to_owned(), json!("default"));
+    object.insert("profiles".to_owned(), json!({ "default": bindings }));
+    Ok(document)
+}
+
+/// Version 3 added the Caps Lock + Escape toggle gesture. Escape becomes a
+/// reserved key, so files that bind it in some profile get no gesture instead.
+fn toggle_hotkey(mut document: Value) -> Result<Value> {
+    let object = document
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("the configuration is not a JSON object"))?;
+    let escape_bound = object
+        .get("profiles")
+        .and_then(Value::as_object)
+        .ok_or_else(|| anyhow!("the configuration has no profiles"))?
+        .values()
+        .filter_map(Value::as_object)
+        .any(|bindings| bindings.values().any(|key| key == "escape"));
+    let toggle = if escape_bound {
+        Value::Null
+    } else {
+        json!({ "key": "escape", "hold_ms": 1000 })
+    };
+    object.insert("toggle".to_owned(), toggle);
+    Ok(document)
+}
+
+/// Version 4 added opt-in usage statistics, off for everyone who upgrades.
+fn usage_statistics(mut document: Value) -> Result<Value> {
+    document
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("the configuration is not a JSON object"))?
+        .insert("statistics".to_owned(), json!(false));
+    Ok(document)
+}
+
+/// Version 5 added window themes; files from before keep the green one.
+fn window_theme(mut document: Value) -> Result<Value> {
+    document
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("the configuration is not a JSON object"))?
+        .insert("theme".to_owned(), json!("crt_green"));
+    Ok(document)
+}
+
+/// Version 6 added the window language; files from before follow the system.
+fn window_language(mut document: Value) -> Result<Value> {
+    document
+        .as_object_mut()
+        .ok_or_else(|| anyhow!("the configuration is not a JSON object"))?
+        .insert("language".to_owned(), json!("system"));
+    Ok(document)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Action, InputKey};
+
+    /// One file per historical format, as a user of that release would have it.
+    const FIXTURES: &[(u32, &str)] = &[
+        (1, include_str!("../config/migrations/v1.json")),
+        (2, include_str!("../config/migrations/v2.json")),
+        (3, include_str!("../config/migrations/v3.json")),
+        (4, include_str!("../config/migrations/v4.json")),
+        (5, include_str!("../config/migrations/v5.json")),
+        (6, include_str!("../config/migrations/v6.json")),
+    ];
+
+    #[test]
+    fn every_historical_version_has_a_fixture() {
+        let versions: Vec<u32> = FIXTURES.iter().map(|(version, _)| *version).collect();
+        assert_eq!(versions, (1..=CONFIG_VERSION).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn fixtures_upgrade_to_a_valid_current_config() {
+        for (version, json) in FIXTURES {
+            let upgrade = upgrade(json.as_bytes()).unwrap();
+            assert_eq!(upgrade.config.version, CONFIG_VERSION);
+            assert_eq!(
+                upgrade.upgraded_from,
+                (*version < CONFIG_VERSION).then_some(*version)
+            );
+            upgrade.config.validate().unwrap();
+        }
+    }
+
+    #[test]
+    fn version_one_bindings_become_the_default_profile() {
+        let config = upgrade(FIXTURES[0].1.as_bytes()).unwrap().config;
+        assert_eq!(config.profile, "default");
+        assert_eq!(config.profiles.len(), 1);
+        assert_eq!(config.key_for(Action::PageUp), Some(InputKey::P));
+        assert!(!config.enabled);
+    }
+
+    #[test]
+    fn the_toggle_gesture_never_takes_a_bound_escape_key() {
+        let config = upgrade(FIXTURES[1].1.as_bytes()).unwrap().config;
+        assert_eq!(
+            config.toggle.map(|toggle| toggle.key),
+            Some(InputKey::Escape)
+        );
+
+        let escape_bound = br#"{"version": 2, "enabled": true, "profile": "default",
+            "profiles": {"default": {"arrow_up": "i"}, "vim": {"escape": "escape"}}}"#;
+        let config = upgrade(escape_bound).unwrap().config;
+        assert_eq!(config.toggle, None);
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn newer_and_missing_versions_are_rejected() {
+        let newer = format!(
+            r#"{{"version": {}, "enabled": true, "profile": "default", "profiles": {{"default": {{}}}}}}"#,
+            CONFIG_VERSION + 1
+        );
+        assert!(upgrade(newer.as_bytes()).is_err());
+        assert!(upgrade(br#"{"enabled": true, "bindings": {}}"#).is_err());
+        assert!(upgrade(br#"{"version": 0, "enabled": true, "bindings": {}}"#).is_err());
+    }
+}
@@ -0,0 +1,98 @@
+use crate::config::{InputKey, ToggleHotkey};
+use std::time::Instant;
+
+/// What a key event meant to the toggle gesture.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Step {
+    /// Not the toggle key, or Caps Lock is not held.
+    Unrelated,
+    /// The toggle key moved while Caps Lock is held.
+    Holding,
+    /// The toggle key has now been held long enough; fires once per hold.
+    Complete,
+}
+
+/// Recognizes Caps Lock plus the toggle key held together. Each input thread
+/// keeps its own and feeds it every key event whether or not remapping is
+/// enabled, since the gesture is also how remapping is switched back on.
+/// Holding is measured on the key's auto-repeat, so no timer is needed.
+#[derive(Default)]
+pub struct ToggleGesture {
+    caps_down: bool,
+    pressed_at: Option<Instant>,
+    complete: bool,
+}
+
+impl ToggleGesture {
+    pub fn caps(&mut self, down: bool) {
+        self.caps_down = down;
+        if !down {
+            self.reset();
+        }
+    }
+
+    /// Feeds any other key; `down` is true for presses and repeats alike.
+    pub fn key(
+        &mut self,
+        key: Option<InputKey>,
+        down: bool,
+        hotkey: Option<ToggleHotkey>,
+        now: Instant,
+    ) -> Step {
+        let Some(hotkey) = hotkey.filter(|hotkey| key == Some(hotkey.key)) else {
+            return Step::Unrelated;
+        };
+        if !self.caps_down {
+            return Step::Unrelated;
+        }
+        if !down {
+            self.reset();
+            return Step::Holding;
+        }
+        let pressed_at = *self.pressed_at.get_or_insert(now);
+        if !self.complete && now.duration_since(pressed_at) >= hotkey.hold() {
+            self.complete = true;
+            return Step::Complete;
+        }
+        Step::Holding
+    }
+
+    fn reset(&mut self) {
+        self.pressed_at = None;
+        self.complete = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn completes_once_per_hold_with_caps_lock_down() {
+        let hotkey = Some(ToggleHotkey {
+            key: InputKey::Escape,
+            hold_ms: 1000,
+        });
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let escape = Some(InputKey::Escape);
+        let mut gesture = ToggleGesture::default();
+
+        assert_eq!(gesture.key(escape, true, hotkey, at(0)), Step::Unrelated);
+        gesture.caps(true);
+        assert_eq!(
+            gesture.key(Some(InputKey::A), true, hotkey, at(0)),
+            Step::Unrelated
+        );
+        assert_eq!(gesture.key(escape, true, hotkey, at(0)), Step::Holding);
+        assert_eq!(gesture.key(escape, true, hotkey, at(600)), Step::Holding);
+        assert_eq!(gesture.key(escape, true, hotkey, at(1000)), Step::Complete);
+        assert_eq!(gesture.key(escape, true, hotkey, at(1400)), Step::Holding);
+
+        assert_eq!(gesture.key(escape, false, hotkey, at(1500)), Step::Holding);
+        assert_eq!(gesture.key(escape, true, hotkey, at(1600)), Step::Holding);
+        assert_eq!(gesture.key(escape, true, hotkey, at(2600)), Step::Complete);
+        assert_eq!(gesture.key(escape, true, None, at(4000)), Step::Unrelated);
+    }
+}
@@ -1,13 +1,36 @@
+use super::gesture::{Step, ToggleGesture};
+use super::watchdog::{self, Heartbeat};
 use crate::config::{Action, InputKey, RuntimeBindings};
+use crate::diagnostics::{DeviceReport, OutputReport, Worker, WorkerState};
+use crate::inspector::Output;
 use anyhow::{anyhow, Context, Result};
 use evdev::uinput::VirtualDevice;
 use evdev::{AttributeSet, Device, EventType, InputEvent, KeyCode};
 use log::{error, info};
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::{Arc, Mutex};
+use std::io;
+use std::os::fd::{AsFd, AsRawFd, FromRawFd, OwnedFd};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, TryLockError};
 use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
 static LAYER_HOLDERS: AtomicUsize = AtomicUsize::new(0);
+/// Set once the watchdog has given up on the workers; they exit quietly.
+static ABANDONED: AtomicBool = AtomicBool::new(false);
+/// `EVIOCGRAB` from linux/input.h.
+const EVIOCGRAB: libc::Ioctl = libc::_IOW::<libc::c_int>(b'E' as u32, 0x90);
+/// Both Shift keys and Escape held this long release every keyboard. The chord
+/// is hard-coded so it works whatever the configuration says.
+const EMERGENCY_HOLD: Duration = Duration::from_secs(2);
+const VIRTUAL_KEYBOARD_NAME: &str = "nocaps virtual keyboard";
+/// What a device must be able to type to be taken as a keyboard.
+const KEYBOARD_KEYS: [KeyCode; 4] = [
+    KeyCode::KEY_CAPSLOCK,
+    KeyCode::KEY_A,
+    KeyCode::KEY_Z,
+    KeyCode::KEY_ENTER,
+];
 
 pub struct KeyboardManager {
     _threads: Vec<JoinHandle<()>>,
@@ -15,10 +38,36 @@ pub struct KeyboardManager {
 
 impl KeyboardManager {
     pub fn new(runtime: Arc<RuntimeBindings>) -> Result<Self> {
-        let mut devices: Vec<Device> = evdev::enumerate()
-            .map(|(_, device)| device)
-            .filter(is_keyboard)
-            .collect();
+        // Every node is reported, with why it was taken or skipped, so the
+        // diagnostics panel can explain a keyboard that is not remapped.
+        let mut devices = Vec::new();
+        let mut reports = Vec::new();
+        for (path, opened) in event_nodes() {
+            let path = path.display().to_string();
+            let device = match opened {
+                Ok(device) => device,
+                Err(error) => {
+                    let reason = format!("cannot open: {error}");
+                    let name = String::new();
+                    reports.push(DeviceReport::new(path, name, String::new(), false, reason));
+                    continue;
+                }
+            };
+            let id = device.input_id();
+            let (included, reason) = keyboard_verdict(&device);
+            let mut report = DeviceReport::new(
+                path,
+                device.name().unwrap_or_default().to_owned(),
+                format!("{} {:04x}:{:04x}", id.bus_type(), id.vendor(), id.product()),
+                included,
+                reason,
+            );
+            if included {
+                devices.push((device, report.watch()));
+            }
+            reports.push(report);
+        }
+        runtime.diagnostics().found(reports);
 
         if devices.is_empty() {
             return Err(anyhow!(
@@ -27,7 +76,7 @@ impl KeyboardManager {
         }
 
         let mut supported = AttributeSet::<KeyCode>::new();
-        for device in &devices {
+        for (device, _) in &devices {
             if let Some(keys) = device.supported_keys() {
                 for key in keys {
                     supported.insert(key);
@@ -38,70 +87,398 @@ impl KeyboardManager {
             supported.insert(linux_action_code(*action));
         }
 
-        let virtual_keyboard = VirtualDevice::builder()
+        let mut virtual_keyboard = VirtualDevice::builder()
             .context("open /dev/uinput")?
-            .name("nocaps virtual keyboard")
+            .name(VIRTUAL_KEYBOARD_NAME)
             .with_keys(&supported)
             .context("configure virtual keyboard")?
             .build()
             .context("create virtual keyboard")?;
+        runtime.diagnostics().created(OutputReport {
+            name: VIRTUAL_KEYBOARD_NAME.to_owned(),
+            path: virtual_keyboard
+                .enumerate_dev_nodes_blocking()
+                .ok()
+                .and_then(|mut nodes| nodes.next()?.ok())
+                .map(|path| path.display().to_string()),
+            keys: supported.iter().map(|key| key.code()).collect(),
+        });
         let virtual_keyboard = Arc::new(Mutex::new(virtual_keyboard));
 
-        for device in &mut devices {
-            device
-                .grab()
-                .with_context(|| format!("grab {}", device.name().unwrap_or("keyboard")))?;
+        // Every keyboard can be let go from any thread, so the emergency chord
+        // and the watchdog release them all at once, busy or not.
+        let mut keyboards = Vec::with_capacity(devices.len());
+        for (device, _) in &devices {
+            let name = device.name().unwrap_or("keyboard").to_owned();
+            keyboards.push(Arc::new(Keyboard::new(name, device)?));
+        }
+        let keyboards: Arc<[Arc<Keyboard>]> = keyboards.into();
+        let woken = Arc::clone(&keyboards);
+        runtime.on_change(move || woken.iter().for_each(|keyboard| keyboard.wake()));
+
+        // While remapping is disabled the keyboards are left alone; workers take
+        // them when it is switched on.
+        if runtime.is_enabled() {
+            for keyboard in keyboards.iter() {
+                keyboard
+                    .grab()
+                    .with_context(|| format!("grab {}", keyboard.name))?;
+            }
         }
 
         let mut threads = Vec::with_capacity(devices.len());
-        for device in devices {
+        for ((device, worker), keyboard) in devices.into_iter().zip(keyboards.iter()) {
             let runtime = runtime.clone();
             let output = virtual_keyboard.clone();
-            let name = device.name().unwrap_or("keyboard").to_owned();
+            let keyboard = Arc::clone(keyboard);
+            let keyboards = Arc::clone(&keyboards);
             threads.push(
                 thread::Builder::new()
-                    .name(format!("nocaps-{name}"))
-                    .spawn(move || run_device(device, runtime, output, &name))
+                    .name(format!("nocaps-{}", keyboard.name))
+                    .spawn(move || {
+                        run_device(device, runtime, output, &keyboard, &keyboards, &worker)
+                    })
                     .context("start Linux keyboard worker")?,
             );
         }
 
+        let heartbeats = keyboards
+            .iter()
+            .flat_map(|keyboard| [Arc::clone(&keyboard.beat), Arc::clone(&keyboard.emitter)])
+            .collect();
+        let output = Arc::clone(&virtual_keyboard);
+        let watched = Arc::clone(&runtime);
+        watchdog::supervise(heartbeats, move |stalled| {
+            abandon(stalled, &keyboards, &output, &supported, &watched)
+        })?;
+
+        // Grabbed keyboards offer every key press, so the window can capture
+        // through them.
+        runtime.offer_capture(true);
+
         info!("remapping {} Linux keyboard device(s)", threads.len());
         Ok(Self { _threads: threads })
     }
 }
 
-fn is_keyboard(device: &Device) -> bool {
-    device.supported_keys().is_some_and(|keys| {
-        keys.contains(KeyCode::KEY_CAPSLOCK)
-            && keys.contains(KeyCode::KEY_A)
-            && keys.contains(KeyCode::KEY_Z)
-            && keys.contains(KeyCode::KEY_ENTER)
-    })
+/// What other threads share of one keyboard: its grab, changed through a
+/// duplicate of the worker's descriptor so the keyboard can be let go while
+/// its worker is blocked or stuck, the heartbeats the watchdog checks, and a
+/// way to wake the worker.
+struct Keyboard {
+    name: String,
+    grabbed: AtomicBool,
+    set_grab: Box<dyn Fn(bool) -> io::Result<()> + Send + Sync>,
+    // An eventfd the worker polls next to the device, so changes that need no
+    // key press, such as disabling remapping, are applied at once.
+    wake: OwnedFd,
+    beat: Arc<Heartbeat>,
+    // Each worker times its own writes; a shared beat would let one worker's
+    // idle wipe out another's stuck write.
+    emitter: Arc<Heartbeat>,
+}
+
+impl Keyboard {
+    fn new(name: String, device: &Device) -> Result<Self> {
+        let fd = device
+            .as_fd()
+            .try_clone_to_owned()
+            .with_context(|| format!("duplicate the descriptor of {name}"))?;
+        Self::with_grab(name, move |grab| {
+            // SAFETY: EVIOCGRAB takes its int argument by value.
+            match unsafe { libc::ioctl(fd.as_raw_fd(), EVIOCGRAB, libc::c_int::from(grab)) } {
+                0 => Ok(()),
+                _ => Err(io::Error::last_os_error()),
+            }
+        })
+    }
+
+    fn with_grab(
+        name: String,
+        set_grab: impl Fn(bool) -> io::Result<()> + Send + Sync + 'static,
+    ) -> Result<Self> {
+        // SAFETY: eventfd returns a new descriptor, which is owned from here on,
+        // or -1.
+        let wake = match unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) } {
+            -1 => return Err(io::Error::last_os_error()).context("create an eventfd"),
+            fd => unsafe { OwnedFd::from_raw_fd(fd) },
+        };
+        Ok(Self {
+            beat: Heartbeat::new(format!("the worker for {name}")),
+            emitter: Heartbeat::new(format!("the virtual keyboard, writing for {name}")),
+            name,
+            grabbed: AtomicBool::new(false),
+            set_grab: Box::new(set_grab),
+            wake,
+        })
+    }
+
+    fn is_grabbed(&self) -> bool {
+        self.grabbed.load(Ordering::SeqCst)
+    }
+
+    fn grab(&self) -> io::Result<()> {
+        (self.set_grab)(true)?;
+        self.grabbed.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Wakes the worker if it is waiting for input.
+    fn wake(&self) {
+        let one = 1u64.to_ne_bytes();
+        // SAFETY: writes eight bytes from a live buffer. A full counter means
+        // the worker is due to wake anyway.
+        unsafe { libc::write(self.wake.as_raw_fd(), one.as_ptr().cast(), one.len()) };
+    }
+
+    /// Waits for input on `device` or a `wake`; true when there is input.
+    fn wait(&self, device: &Device) -> io::Result<bool> {
+        let pollfd = |fd| libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let mut fds = [pollfd(device.as_raw_fd()), pollfd(self.wake.as_raw_fd())];
+        // SAFETY: `fds` is a live array of two pollfds.
+        while unsafe { libc::poll(fds.as_mut_ptr(), 2, -1) } < 0 {
+            let error = io::Error::last_os_error();
+            if error.kind() != io::ErrorKind::Interrupted {
+                return Err(error);
+            }
+        }
+        if fds[1].revents != 0 {
+            let mut count = [0; 8];
+            // SAFETY: reads the eventfd's eight-byte counter into a live
+            // buffer, which resets it.
+            unsafe {
+                libc::read(
+                    self.wake.as_raw_fd(),
+                    count.as_mut_ptr().cast(),
+                    count.len(),
+                )
+            };
+        }
+        // A hang-up or error counts as input, so reading the device reports it.
+        Ok(fds[0].revents != 0)
+    }
+
+    /// Lets the keyboard go from any thread. False if it was not grabbed.
+    fn release(&self) -> bool {
+        if !self.grabbed.swap(false, Ordering::SeqCst) {
+            return false;
+        }
+        match (self.set_grab)(false) {
+            Ok(()) => info!("released {}", self.name),
+            Err(error) => error!("could not release {}: {error}", self.name),
+        }
+        true
+    }
+}
+
+/// The emergency chord: every keyboard is let go on the spot, not when its
+/// worker next reads a key, and remapping stays off until enabled again.
+fn release_all(runtime: &RuntimeBindings, keyboards: &[Arc<Keyboard>]) {
+    for keyboard in keyboards {
+        keyboard.release();
+    }
+    runtime.emergency_release();
+}
+
+/// Whether `device` is taken as a keyboard, and why.
+pub(super) fn keyboard_verdict(device: &Device) -> (bool, String) {
+    let Some(supported) = device.supported_keys() else {
+        return (false, "reports no keys".to_owned());
+    };
+    let names = |keys: &[KeyCode]| {
+        keys.iter()
+            .map(|key| key_code_name(key.code()))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    let missing: Vec<KeyCode> = KEYBOARD_KEYS
+        .into_iter()
+        .filter(|key| !supported.contains(*key))
+        .collect();
+    if missing.is_empty() {
+        (true, format!("has {}", names(&KEYBOARD_KEYS)))
+    } else {
+        (false, format!("lacks {}", names(&missing)))
+    }
+}
+
+/// Every event node under `/dev/input` in numeric order, opened where this
+/// user may.
+fn event_nodes() -> Vec<(PathBuf, std::io::Result<Device>)> {
+    let number = |path: &PathBuf| {
+        path.file_name()
+            .and_then(|name| name.to_str()?.strip_prefix("event")?.parse::<u32>().ok())
+    };
+    let mut paths: Vec<PathBuf> = std::fs::read_dir("/dev/input")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| number(path).is_some())
+        .collect();
+    paths.sort_by_key(number);
+    paths
+        .into_iter()
+        .map(|path| {
+            let device = Device::open(&path);
+            (path, device)
+        })
+        .collect()
 }
 
 fn run_device(
     mut device: Device,
     runtime: Arc<RuntimeBindings>,
     output: Arc<Mutex<VirtualDevice>>,
-    name: &str,
+    keyboard: &Keyboard,
+    keyboards: &[Arc<Keyboard>],
+    worker: &Worker,
 ) {
     super::elevate_input_thread();
+    let (name, beat, emitter) = (&keyboard.name, &keyboard.beat, &keyboard.emitter);
+    let inspector = runtime.inspector();
+    let inspected = inspector.register(name);
+    let inspect = |source: KeyCode, value: i32, output: Output| {
+        if inspector.is_enabled() {
+            let key = key_from_linux(source);
+            let layer = runtime.is_layer_active();
+            inspector.record(inspected, source.code(), value, key, layer, output);
+        }
+    };
     let mut held_targets = [None; InputKey::COUNT];
     let mut captured_caps = false;
+    let mut gesture = ToggleGesture::default();
+    let mut chord = EmergencyChord::default();
+    // A key taken for the window's capture; its repeats and release are kept
+    // from applications too.
+    let mut capture_release = None;
+    let mut generation = runtime.generation();
     let mut translated = Vec::with_capacity(16);
+    // Whether this worker holds the keyboard. Another thread may let it go
+    // first; the worker then drops what it holds when woken.
+    let mut grabbed = keyboard.is_grabbed();
 
     loop {
         translated.clear();
-        let events = match device.fetch_events() {
-            Ok(events) => events,
+        beat.idle();
+        // Once abandoned, the watchdog's state stands.
+        if !ABANDONED.load(Ordering::SeqCst) {
+            worker.set(if grabbed {
+                WorkerState::Grabbed
+            } else {
+                WorkerState::Released
+            });
+        }
+        let mut input = keyboard.wait(&device);
+        beat.busy();
+        if ABANDONED.load(Ordering::SeqCst) {
+            return;
+        }
+
+        // The bindings changed: outputs held under the old ones are let go
+        // now, while their keys may still be held, rather than on the next
+        // key press.
+        if runtime.generation() != generation {
+            generation = runtime.generation();
+            release_targets(&mut held_targets, &output);
+        }
+
+        // Remapping was switched on or off, or the emergency chord let this
+        // keyboard go from another worker. The grab follows before any more
+        // input is read, so no key goes down on one side of it and up on the
+        // other.
+        if grabbed && (!runtime.is_enabled() || !keyboard.is_grabbed()) {
+            // Keys queued while grabbed never reached the desktop; they are
+            // typed as they are, since remapping is off from here on.
+            translated.clear();
+            let _ = take_pending(&mut device, |event| {
+                // Caps Lock only ever worked the layer, so it is not typed.
+                if event.code() != KeyCode::KEY_CAPSLOCK.code() {
+                    inspect(
+                        KeyCode::new(event.code()),
+                        event.value(),
+                        Output::Emitted(event.code()),
+                    );
+                    translated.push(event);
+                }
+            });
+            if !translated.is_empty() {
+                if let Ok(mut output) = output.lock() {
+                    let _ = output.emit(&translated);
+                }
+            }
+            release_everything(&device, captured_caps, &mut held_targets, &output, &runtime);
+            captured_caps = false;
+            keyboard.release();
+            grabbed = false;
+            input = input.map(|_| false);
+        } else if !grabbed && runtime.is_enabled() && regrab(&device, keyboard) {
+            // Keys queued before the grab already reached the desktop.
+            let _ = take_pending(&mut device, |_| {});
+            grabbed = true;
+            gesture = ToggleGesture::default();
+            chord = EmergencyChord::default();
+            input = input.map(|_| false);
+        }
+
+        // Woken without input when only the grab needed to change.
+        let events = match input.and_then(|input| input.then(|| device.fetch_events()).transpose())
+        {
+            Ok(Some(events)) => events,
+            Ok(None) => continue,
             Err(error) => {
+                cleanup_state(captured_caps, &mut held_targets, &output, &runtime);
+                // The worker is gone for good, so the watchdog must not
+                // mistake it for one stuck on its batch.
+                beat.idle();
+                // An unplugged keyboard takes its grab with it. That is no
+                // failure: the other keyboards are still remapped.
+                if error.raw_os_error() == Some(libc::ENODEV) {
+                    info!("{name} was unplugged");
+                    keyboard.grabbed.store(false, Ordering::SeqCst);
+                    worker.set(WorkerState::Unplugged);
+                    return;
+                }
                 error!("stopped reading {name}: {error}");
-                cleanup_state(captured_caps, &held_targets, &output);
+                worker.stop(error.to_string());
+                runtime.report_failure(format!("stopped reading {name}: {error}"));
                 return;
             }
         };
 
+        if !grabbed {
+            // Remapping is off, so the desktop already received these events from
+            // the device itself. Only the toggle gesture is watched, as it can
+            // switch remapping back on.
+            for event in events.filter(|event| event.event_type() == EventType::KEY) {
+                let source = KeyCode::new(event.code());
+                // Not offered for a capture: the key reaches the window as
+                // well, which captures it itself while remapping is off.
+                inspect(source, event.value(), Output::Ungrabbed);
+                if source == KeyCode::KEY_CAPSLOCK {
+                    gesture.caps(event.value() != 0);
+                    continue;
+                }
+                let hotkey = runtime.toggle_hotkey();
+                let down = event.value() != 0;
+                if gesture.key(key_from_linux(source), down, hotkey, Instant::now())
+                    == Step::Complete
+                {
+                    runtime.request_toggle();
+                }
+            }
+            if runtime.is_enabled() && regrab(&device, keyboard) {
+                grabbed = true;
+                gesture = ToggleGesture::default();
+                chord = EmergencyChord::default();
+            }
+            continue;
+        }
+
         for event in events {
             if event.event_type() != EventType::KEY {
                 continue;
@@ -109,24 +486,65 @@ fn run_device(
 
             let source = KeyCode::new(event.code());
             let value = event.value();
+            if chord.key(source, value != 0, Instant::now()) {
+                // The rest of the batch is dropped rather than typed, since
+                // the keyboard is no longer held to lift it again. This worker
+                // is woken with the others to let go of what it holds.
+                release_all(&runtime, keyboards);
+                inspect(source, value, Output::Swallowed);
+                break;
+            }
+            if capture_release == Some(source) && value != 1 {
+                if value == 0 {
+                    capture_release = None;
+                }
+                inspect(source, value, Output::Swallowed);
+                continue;
+            }
+            if value == 1
+                && source != KeyCode::KEY_CAPSLOCK
+                && runtime.capture(key_from_linux(source), || key_code_name(source.code()))
+            {
+                capture_release = Some(source);
+                inspect(source, value, Output::Swallowed);
+                continue;
+            }
             if source == KeyCode::KEY_CAPSLOCK {
+                gesture.caps(value != 0);
                 let enabled = runtime.is_enabled();
-                if value == 1 && enabled && !captured_caps {
+                let swallowed = if value == 1 && enabled && !captured_caps {
                     captured_caps = true;
-                    LAYER_HOLDERS.fetch_add(1, Ordering::SeqCst);
-                    continue;
-                }
-                if value == 0 && captured_caps {
+                    hold_layer(&runtime);
+                    true
+                } else if value == 0 && captured_caps {
                     captured_caps = false;
-                    LAYER_HOLDERS.fetch_sub(1, Ordering::SeqCst);
-                    continue;
-                }
-                if captured_caps {
+                    release_layer(&runtime);
+                    true
+                } else {
+                    captured_caps
+                };
+                if swallowed {
+                    inspect(source, value, Output::Swallowed);
                     continue;
                 }
             }
 
             let input_key = key_from_linux(source);
+            let step = gesture.key(
+                input_key,
+                value != 0,
+                runtime.toggle_hotkey(),
+                Instant::now(),
+            );
+            if step == Step::Complete {
+                runtime.request_toggle();
+            }
+            // The toggle key is reserved while the layer is held; with remapping
+            // disabled it passes through untouched.
+            if step != Step::Unrelated && captured_caps {
+                inspect(source, value, Output::Swallowed);
+                continue;
+            }
             let target = if value == 0 {
                 input_key
                     .and_then(|key| held_targets[key.index()].take())
@@ -136,46 +554,202 @@ fn run_device(
                     .and_then(|key| held_targets[key.index()])
                     .unwrap_or(source)
             } else if LAYER_HOLDERS.load(Ordering::SeqCst) > 0 {
-                let target = input_key
-                    .and_then(|key| runtime.action_for(key))
-                    .map(linux_action_code)
-                    .unwrap_or(source);
-                if let Some(key) = input_key.filter(|_| target != source) {
-                    held_targets[key.index()] = Some(target);
+                match input_key.and_then(|key| Some((key, runtime.action_for(key)?))) {
+                    Some((key, action)) => {
+                        let target = linux_action_code(action);
+                        held_targets[key.index()] = Some(target);
+                        runtime.record_use(key, action);
+                        target
+                    }
+                    None => source,
                 }
-                target
             } else {
                 source
             };
 
+            inspect(source, value, Output::Emitted(target.code()));
             translated.push(InputEvent::new(EventType::KEY.0, target.code(), value));
         }
 
+        if ABANDONED.load(Ordering::SeqCst) {
+            return;
+        }
         if !translated.is_empty() {
+            emitter.busy();
             let result = output
                 .lock()
                 .map_err(|_| anyhow!("virtual keyboard lock is poisoned"))
                 .and_then(|mut output| output.emit(&translated).context("emit keyboard events"));
+            emitter.idle();
             if let Err(error) = result {
                 error!("stopped writing events for {name}: {error:#}");
-                cleanup_state(captured_caps, &held_targets, &output);
+                worker.stop(format!("{error:#}"));
+                cleanup_state(captured_caps, &mut held_targets, &output, &runtime);
+                runtime.report_failure(format!("stopped writing events for {name}: {error:#}"));
                 return;
             }
         }
     }
 }
 
+/// Called by the watchdog when a worker or the virtual keyboard stops making
+/// progress. Lets every keyboard go, lifts every key the virtual keyboard could
+/// be holding, and stops the workers for good.
+fn abandon(
+    stalled: &str,
+    keyboards: &[Arc<Keyboard>],
+    output: &Mutex<VirtualDevice>,
+    supported: &AttributeSet<KeyCode>,
+    runtime: &RuntimeBindings,
+) {
+    ABANDONED.store(true, Ordering::SeqCst);
+    runtime.diagnostics().abandon(stalled);
+    runtime.offer_capture(false);
+    for keyboard in keyboards {
+        keyboard.release();
+    }
+
+    // A stuck holder may never let go of the lock, so only wait briefly. A
+    // poisoned lock still holds a usable device.
+    let releases: Vec<InputEvent> = supported
+        .iter()
+        .map(|key| InputEvent::new(EventType::KEY.0, key.code(), 0))
+        .collect();
+    let mut released = false;
+    for _ in 0..10 {
+        let device = match output.try_lock() {
+            Ok(device) => Some(device),
+            Err(TryLockError::Poisoned(poisoned)) => Some(poisoned.into_inner()),
+            Err(TryLockError::WouldBlock) => None,
+        };
+        if let Some(mut device) = device {
+            released = device.emit(&releases).is_ok();
+            break;
+        }
+        thread::sleep(Duration::from_millis(100));
+    }
+    if !released {
+        error!("could not release keys held on the virtual keyboard");
+    }
+    LAYER_HOLDERS.store(0, Ordering::SeqCst);
+    runtime.set_layer_active(false);
+
+    let message = format!(
+        "{stalled} stopped responding, so every keyboard was released. Restart nocaps to remap again."
+    );
+    error!("{message}");
+    runtime.report_failure(message);
+}
+
+/// Takes the device once remapping is on, but only between keystrokes, so no
+/// key goes down on one side of the grab and up on the other.
+fn regrab(device: &Device, keyboard: &Keyboard) -> bool {
+    let idle = device
+        .get_key_state()
+        .is_ok_and(|keys| keys.iter().next().is_none());
+    if !idle {
+        return false;
+    }
+    match keyboard.grab() {
+        Ok(()) => {
+            info!("grabbed {}", keyboard.name);
+            true
+        }
+        Err(error) => {
+            error!("could not grab {}: {error}", keyboard.name);
+            false
+        }
+    }
+}
+
+/// Reads the key events already queued on `device`, without waiting for more,
+/// and hands each to `handle`. Used as the grab changes, so events read by the
+/// kernel on one side of it are not handled as if on the other.
+fn take_pending(device: &mut Device, mut handle: impl FnMut(InputEvent)) -> io::Result<()> {
+    let mut pollfd = libc::pollfd {
+        fd: device.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    // SAFETY: `pollfd` is a live pollfd, polled without blocking.
+    while unsafe { libc::poll(&mut pollfd, 1, 0) } > 0 && pollfd.revents & libc::POLLIN != 0 {
+        for event in device.fetch_events()? {
+            if event.event_type() == EventType::KEY {
+                handle(event);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Tracks both Shift keys and Escape on one keyboard.
+#[derive(Default)]
+struct EmergencyChord {
+    left_shift: bool,
+    right_shift: bool,
+    escape: bool,
+    since: Option<Instant>,
+    fired: bool,
+}
+
+impl EmergencyChord {
+    /// Feeds every key event; true once per hold, as the chord reaches
+    /// `EMERGENCY_HOLD`. Escape's auto-repeat keeps events coming meanwhile.
+    fn key(&mut self, code: KeyCode, down: bool, now: Instant) -> bool {
+        match code {
+            KeyCode::KEY_LEFTSHIFT => self.left_shift = down,
+            KeyCode::KEY_RIGHTSHIFT => self.right_shift = down,
+            KeyCode::KEY_ESC => self.escape = down,
+            _ => return false,
+        }
+        if !(self.left_shift && self.right_shift && self.escape) {
+            self.since = None;
+            self.fired = false;
+            return false;
+        }
+        let since = *self.since.get_or_insert(now);
+        if self.fired || now.duration_since(since) < EMERGENCY_HOLD {
+            return false;
+        }
+        self.fired = true;
+        true
+    }
+}
+
+/// The layer is active while Caps Lock is held on any keyboard.
+fn hold_layer(runtime: &RuntimeBindings) {
+    if LAYER_HOLDERS.fetch_add(1, Ordering::SeqCst) == 0 {
+        runtime.set_layer_active(true);
+    }
+}
+
+fn release_layer(runtime: &RuntimeBindings) {
+    if LAYER_HOLDERS.fetch_sub(1, Ordering::SeqCst) == 1 {
+        runtime.set_layer_active(false);
+    }
+}
+
 fn cleanup_state(
     captured_caps: bool,
-    held_targets: &[Option<KeyCode>; InputKey::COUNT],
+    held_targets: &mut [Option<KeyCode>; InputKey::COUNT],
     output: &Arc<Mutex<VirtualDevice>>,
+    runtime: &RuntimeBindings,
 ) {
     if captured_caps {
-        LAYER_HOLDERS.fetch_sub(1, Ordering::SeqCst);
+        release_layer(runtime);
     }
+    release_targets(held_targets, output);
+}
+
+/// Lifts the remapped outputs this keyboard holds and forgets them, so the
+/// physical releases later pass through as plain key-ups the kernel ignores.
+fn release_targets(
+    held_targets: &mut [Option<KeyCode>; InputKey::COUNT],
+    output: &Mutex<VirtualDevice>,
+) {
     let releases: Vec<InputEvent> = held_targets
-        .iter()
-        .flatten()
+        .iter_mut()
+        .filter_map(Option::take)
         .map(|key| InputEvent::new(EventType::KEY.0, key.code(), 0))
         .collect();
     if !releases.is_empty() {
@@ -185,6 +759,36 @@ fn cleanup_state(
     }
 }
 
+/// Lifts everything this keyboard holds down on the virtual keyboard: the
+/// layer, remapped targets, and the physical keys that were passed through.
+fn release_everything(
+    device: &Device,
+    captured_caps: bool,
+    held_targets: &mut [Option<KeyCode>; InputKey::COUNT],
+    output: &Arc<Mutex<VirtualDevice>>,
+    runtime: &RuntimeBindings,
+) {
+    cleanup_state(captured_caps, held_targets, output, runtime);
+    let releases: Vec<InputEvent> = device
+        .get_key_state()
+        .map(|keys| {
+            keys.iter()
+                .map(|key| InputEvent::new(EventType::KEY.0, key.code(), 0))
+                .collect()
+        })
+        .unwrap_or_default();
+    if !releases.is_empty() {
+        if let Ok(mut output) = output.lock() {
+            let _ = output.emit(&releases);
+        }
+    }
+}
+
+/// The evdev name of a raw key code, such as `KEY_CAPSLOCK`.
+pub fn key_code_name(code: u16) -> String {
+    format!("{:?}", KeyCode::new(code))
+}
+
 fn key_from_linux(code: KeyCode) -> Option<InputKey> {
     Some(match code {
         KeyCode::KEY_A => InputKey::A,
@@ -305,3 +909,70 @@ fn linux_action_code(action: Action) -> KeyCode {
         Action::MediaNext => KeyCode::KEY_NEXTSONG,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn the_emergency_chord_releases_keyboards_nobody_is_typing_on() {
+        let runtime = RuntimeBindings::new(&Config::default()).unwrap();
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let keyboards: Vec<Arc<Keyboard>> = ["internal", "external"]
+            .into_iter()
+            .map(|name| {
+                let calls = Arc::clone(&calls);
+                let keyboard = Keyboard::with_grab(name.to_owned(), move |grab| {
+                    calls.lock().unwrap().push((name, grab));
+                    Ok(())
+                });
+                Arc::new(keyboard.unwrap())
+            })
+            .collect();
+        for keyboard in &keyboards {
+            keyboard.grab().unwrap();
+        }
+        let woken: Arc<[Arc<Keyboard>]> = keyboards.clone().into();
+        runtime.on_change(move || woken.iter().for_each(|keyboard| keyboard.wake()));
+
+        // Chorded on the internal keyboard; the external one has no events
+        // pending and its worker is blocked reading.
+        release_all(&runtime, &keyboards);
+        assert!(!keyboards[1].is_grabbed());
+        assert_eq!(
+            *calls.lock().unwrap(),
+            [
+                ("internal", true),
+                ("external", true),
+                ("internal", false),
+                ("external", false)
+            ]
+        );
+        assert!(!runtime.is_enabled());
+        assert!(!keyboards[1].release());
+        // Its worker is woken to let go of what it holds on the virtual keyboard.
+        let mut count = [0u8; 8];
+        let read =
+            unsafe { libc::read(keyboards[1].wake.as_raw_fd(), count.as_mut_ptr().cast(), 8) };
+        assert_eq!((read, u64::from_ne_bytes(count)), (8, 1));
+    }
+
+    #[test]
+    fn the_emergency_chord_needs_all_three_keys_for_two_seconds() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut chord = EmergencyChord::default();
+        assert!(!chord.key(KeyCode::KEY_LEFTSHIFT, true, at(0)));
+        assert!(!chord.key(KeyCode::KEY_ESC, true, at(0)));
+        assert!(!chord.key(KeyCode::KEY_ESC, true, at(2500)));
+        assert!(!chord.key(KeyCode::KEY_RIGHTSHIFT, true, at(2600)));
+        assert!(!chord.key(KeyCode::KEY_ESC, true, at(4000)));
+        assert!(chord.key(KeyCode::KEY_ESC, true, at(4600)));
+        assert!(!chord.key(KeyCode::KEY_ESC, true, at(7000)));
+
+        assert!(!chord.key(KeyCode::KEY_RIGHTSHIFT, false, at(7100)));
+        assert!(!chord.key(KeyCode::KEY_RIGHTSHIFT, true, at(7200)));
+        assert!(chord.key(KeyCode::KEY_ESC, true, at(9200)));
+    }
+}
@@ -1,15 +1,20 @@
+use super::gesture::{Step, ToggleGesture};
 use crate::config::{Action, InputKey, RuntimeBindings};
 use anyhow::{anyhow, Context, Result};
 use enigo::{Direction, Enigo, Key as EnigoKey, Keyboard, Settings};
 use log::{error, info};
 use rdev::{grab, Event, EventType, Key};
-use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
 use std::sync::{Arc, Mutex, OnceLock};
 use std::thread::{self, JoinHandle};
+use std::time::Instant;
 
 static CAPS_CAPTURED: AtomicBool = AtomicBool::new(false);
 static INJECTING: AtomicBool = AtomicBool::new(false);
 static HELD_ACTIONS: [AtomicU8; InputKey::COUNT] = [const { AtomicU8::new(0) }; InputKey::COUNT];
+// The `RuntimeBindings` generation that `HELD_ACTIONS` were pressed under.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
 static ENIGO: OnceLock<Mutex<Enigo>> = OnceLock::new();
 
 pub struct KeyboardManager {
@@ -28,8 +33,10 @@ impl KeyboardManager {
             .name("nocaps-macos-input".to_owned())
             .spawn(move || {
                 super::elevate_input_thread();
-                if let Err(error) = grab(move |event| callback(event, &runtime)) {
+                let capture = Arc::clone(&runtime);
+                if let Err(error) = grab(move |event| callback(event, &capture)) {
                     error!("macOS keyboard capture stopped: {error:?}");
+                    runtime.report_failure(format!("macOS keyboard capture stopped: {error:?}"));
                 }
             })
             .context("start macOS keyboard worker")?;
@@ -38,11 +45,26 @@ impl KeyboardManager {
     }
 }
 
+thread_local! {
+    // `grab` calls back on the capture thread only.
+    static GESTURE: RefCell<ToggleGesture> = RefCell::new(ToggleGesture::default());
+}
+
 fn callback(event: Event, runtime: &RuntimeBindings) -> Option<Event> {
     if INJECTING.load(Ordering::SeqCst) {
         return Some(event);
     }
 
+    // Bindings changed since the last key: let go of actions held under the old ones.
+    let generation = runtime.generation();
+    if GENERATION.swap(generation, Ordering::SeqCst) != generation {
+        for held in &HELD_ACTIONS {
+            if let Some(action) = Action::from_held_code(held.swap(0, Ordering::SeqCst)) {
+                inject(action, Direction::Release);
+            }
+        }
+    }
+
     let (source, down) = match event.event_type {
         EventType::KeyPress(key) => (key, true),
         EventType::KeyRelease(key) => (key, false),
@@ -50,11 +72,14 @@ fn callback(event: Event, runtime: &RuntimeBindings) -> Option<Event> {
     };
 
     if source == Key::CapsLock {
+        GESTURE.with_borrow_mut(|gesture| gesture.caps(down));
         if down && runtime.is_enabled() {
             CAPS_CAPTURED.store(true, Ordering::SeqCst);
+            runtime.set_layer_active(true);
             return None;
         }
         if !down && CAPS_CAPTURED.swap(false, Ordering::SeqCst) {
+            runtime.set_layer_active(false);
             return None;
         }
     }
@@ -63,6 +88,18 @@ fn callback(event: Event, runtime: &RuntimeBindings) -> Option<Event> {
         return Some(event);
     };
 
+    let hotkey = runtime.toggle_hotkey();
+    let step = GESTURE
+        .with_borrow_mut(|gesture| gesture.key(Some(input_key), down, hotkey, Instant::now()));
+    if step == Step::Complete {
+        runtime.request_toggle();
+    }
+    // The toggle key is reserved while the layer is held; with remapping
+    // disabled it passes through untouched.
+    if step != Step::Unrelated && CAPS_CAPTURED.load(Ordering::SeqCst) {
+        return None;
+    }
+
     if !down {
         let held = HELD_ACTIONS[input_key.index()].swap(0, Ordering::SeqCst);
         if let Some(action) = Action::from_held_code(held) {
@@ -74,7 +111,11 @@ fn callback(event: Event, runtime: &RuntimeBindings) -> Option<Event> {
     if down && CAPS_CAPTURED.load(Ordering::SeqCst) {
         if let Some(action) = runtime.action_for(input_key) {
             if action_to_enigo(action).is_some() {
-                HELD_ACTIONS[input_key.index()].store(action.held_code(), Ordering::SeqCst);
+                let held = action.held_code();
+                // Auto-repeat finds the action already held and is not counted.
+                if HELD_ACTIONS[input_key.index()].swap(held, Ordering::SeqCst) != held {
+                    runtime.record_use(input_key, action);
+                }
                 inject(action, Direction::Press);
                 return None;
             }
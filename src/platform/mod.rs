@@ -2,13 +2,20 @@ use crate::config::RuntimeBindings;
 use anyhow::Result;
 use std::sync::Arc;
 
+mod gesture;
 #[cfg(target_os = "linux")]
 mod linux;
 #[cfg(target_os = "macos")]
 mod macos;
+#[cfg(all(target_os = "linux", feature = "gui"))]
+pub mod setup;
+#[cfg(target_os = "linux")]
+mod watchdog;
 #[cfg(target_os = "windows")]
 mod windows;
 
+#[cfg(all(target_os = "linux", feature = "gui"))]
+pub use linux::key_code_name;
 #[cfg(target_os = "linux")]
 pub use linux::KeyboardManager;
 #[cfg(target_os = "macos")]
@@ -0,0 +1,307 @@
+use super::linux::keyboard_verdict;
+use anyhow::{anyhow, Context, Result};
+use std::ffi::{CStr, CString};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::Path;
+use std::process::Command;
+
+/// Where `install_permissions` puts `UDEV_RULE`.
+pub const UDEV_RULE_PATH: &str = "/etc/udev/rules.d/99-nocaps.rules";
+
+/// Opens keyboards and `/dev/uinput` to the `input` group. Most distributions
+/// already do the former; `static_node` covers `/dev/uinput` before the module
+/// is loaded, when the node exists only to load it on first open.
+pub const UDEV_RULE: &str = r#"# Installed by nocaps: lets the input group read keyboards and create a virtual one.
+KERNEL=="event*", SUBSYSTEM=="input", ENV{ID_INPUT_KEYBOARD}=="1", MODE="0660", GROUP="input"
+KERNEL=="uinput", SUBSYSTEM=="misc", MODE="0660", GROUP="input", OPTIONS+="static_node=uinput"
+"#;
+
+/// Run as root by `pkexec` with the rule, the user and the rule's path as
+/// arguments, so none of them is ever parsed as shell.
+const INSTALL_SCRIPT: &str = r#"set -e
+groupadd -f --system input
+printf '%s' "$1" > "$3"
+printf 'uinput\n' > /etc/modules-load.d/nocaps.conf
+modprobe uinput
+udevadm control --reload-rules
+udevadm trigger --subsystem-match=input --subsystem-match=misc
+usermod -aG input "$2"
+"#;
+
+/// `CAP_SYS_NICE` from linux/capability.h.
+const CAP_SYS_NICE: u32 = 23;
+
+/// Something nocaps needs from the system, in the order it is checked.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Item {
+    InputGroup,
+    Keyboards,
+    UinputModule,
+    Uinput,
+    Realtime,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum State {
+    Ok,
+    Missing,
+    /// Granted, but only to sessions started after the change.
+    LogInAgain,
+    /// Missing, but nocaps runs without it.
+    Optional,
+}
+
+pub struct Check {
+    pub item: Item,
+    pub state: State,
+    /// Facts behind the state, as the system spells them: device nodes, modes,
+    /// groups. Not translated.
+    pub detail: String,
+}
+
+impl Check {
+    /// Whether this check explains remapping being unavailable.
+    pub fn blocks_remapping(&self) -> bool {
+        matches!(self.item, Item::Keyboards | Item::Uinput) && self.state != State::Ok
+    }
+}
+
+/// Looks at the permissions the Linux backend needs, without changing any.
+pub fn check() -> Vec<Check> {
+    // Before `/dev/uinput` is opened below, which loads the module on demand.
+    let module_loaded = Path::new("/sys/module/uinput").exists();
+    vec![
+        input_group(),
+        keyboards(),
+        Check {
+            item: Item::UinputModule,
+            state: if module_loaded {
+                State::Ok
+            } else {
+                State::Missing
+            },
+            detail: String::new(),
+        },
+        uinput(),
+        realtime(),
+    ]
+}
+
+/// The login name `install_permissions` adds to the `input` group.
+pub fn user_name() -> Option<String> {
+    // SAFETY: getuid cannot fail.
+    account_name(unsafe { libc::getuid() })
+}
+
+/// Installs `UDEV_RULE`, loads `uinput` now and at boot, and adds the user to
+/// the `input` group, asking for an administrator password through polkit.
+/// Blocks until the password dialog is answered.
+pub fn install_permissions() -> Result<()> {
+    let user = user_name().context("look up the current user")?;
+    let output = Command::new("pkexec")
+        .args(["/bin/sh", "-c", INSTALL_SCRIPT, "nocaps-setup"])
+        .args([UDEV_RULE, &user, UDEV_RULE_PATH])
+        .output()
+        .context("run pkexec")?;
+    match output.status.code() {
+        Some(0) => Ok(()),
+        // pkexec exits quietly when its password dialog is dismissed.
+        _ if output.stderr.is_empty() => Err(anyhow!("authentication was dismissed")),
+        _ => Err(anyhow!(
+            "{}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )),
+    }
+}
+
+fn input_group() -> Check {
+    let check = |state, detail: String| Check {
+        item: Item::InputGroup,
+        state,
+        detail,
+    };
+    // SAFETY: geteuid cannot fail.
+    if unsafe { libc::geteuid() } == 0 {
+        return check(State::Ok, "root".to_owned());
+    }
+    let name = CString::new("input").expect("no interior null");
+    // SAFETY: getgrnam returns null or a record that stays valid until the
+    // next group lookup; only the id is read.
+    let gid = unsafe {
+        let group = libc::getgrnam(name.as_ptr());
+        if group.is_null() {
+            return check(State::Missing, "no input group".to_owned());
+        }
+        (*group).gr_gid
+    };
+    if session_groups().contains(&gid) {
+        check(State::Ok, format!("gid {gid}"))
+    } else if user_name().is_some_and(|user| configured_groups(&user).contains(&gid)) {
+        check(State::LogInAgain, format!("gid {gid}"))
+    } else {
+        check(State::Missing, format!("gid {gid}"))
+    }
+}
+
+/// The groups this process runs with, fixed when the session started.
+fn session_groups() -> Vec<libc::gid_t> {
+    // SAFETY: the first call only counts; the second fills at most that many.
+    unsafe {
+        let count = libc::getgroups(0, std::ptr::null_mut());
+        let mut groups = vec![0; count.max(0) as usize];
+        let filled = libc::getgroups(count, groups.as_mut_ptr());
+        groups.truncate(filled.max(0) as usize);
+        groups.push(libc::getegid());
+        groups
+    }
+}
+
+/// The groups `user` gets at the next login, from the group database.
+fn configured_groups(user: &str) -> Vec<libc::gid_t> {
+    let Ok(name) = CString::new(user) else {
+        return Vec::new();
+    };
+    let mut groups: Vec<libc::gid_t> = vec![0; 64];
+    loop {
+        let mut count = groups.len() as libc::c_int;
+        // SAFETY: `count` is the buffer's length; on -1 it holds the length needed.
+        let found = unsafe {
+            libc::getgrouplist(
+                name.as_ptr(),
+                libc::getgid(),
+                groups.as_mut_ptr(),
+                &mut count,
+            )
+        };
+        if found >= 0 {
+            groups.truncate(count as usize);
+            return groups;
+        }
+        if count as usize <= groups.len() {
+            return Vec::new();
+        }
+        groups.resize(count as usize, 0);
+    }
+}
+
+/// Keyboards are counted the way the backend picks them, so a readable mouse
+/// does not make this pass.
+fn keyboards() -> Check {
+    let mut nodes: Vec<String> = evdev::enumerate()
+        .filter(|(_, device)| keyboard_verdict(device).0)
+        .filter_map(|(path, _)| Some(path.file_name()?.to_string_lossy().into_owned()))
+        .collect();
+    nodes.sort();
+    Check {
+        item: Item::Keyboards,
+        state: if nodes.is_empty() {
+            State::Missing
+        } else {
+            State::Ok
+        },
+        detail: nodes.join(" "),
+    }
+}
+
+fn uinput() -> Check {
+    let path = Path::new("/dev/uinput");
+    let Ok(metadata) = path.metadata() else {
+        return Check {
+            item: Item::Uinput,
+            state: State::Missing,
+            detail: "no /dev/uinput".to_owned(),
+        };
+    };
+    let writable = std::fs::OpenOptions::new().write(true).open(path).is_ok();
+    Check {
+        item: Item::Uinput,
+        state: if writable { State::Ok } else { State::Missing },
+        detail: format!(
+            "{:04o} {}:{}",
+            metadata.permissions().mode() & 0o777,
+            account_name(metadata.uid()).unwrap_or_else(|| metadata.uid().to_string()),
+            group_name(metadata.gid())
+        ),
+    }
+}
+
+fn account_name(uid: libc::uid_t) -> Option<String> {
+    // SAFETY: getpwuid returns null or a record that stays valid until the
+    // next passwd lookup; the name is copied out straight away.
+    unsafe {
+        let account = libc::getpwuid(uid);
+        (!account.is_null()).then(|| {
+            CStr::from_ptr((*account).pw_name)
+                .to_string_lossy()
+                .into_owned()
+        })
+    }
+}
+
+fn group_name(gid: libc::gid_t) -> String {
+    // SAFETY: as in `account_name`, for the group database.
+    unsafe {
+        let group = libc::getgrgid(gid);
+        if group.is_null() {
+            gid.to_string()
+        } else {
+            CStr::from_ptr((*group).gr_name)
+                .to_string_lossy()
+                .into_owned()
+        }
+    }
+}
+
+/// Realtime priority comes from `CAP_SYS_NICE` or from an `RLIMIT_RTPRIO`
+/// granted through limits.conf; either lets the input threads use it.
+fn realtime() -> Check {
+    let capable = std::fs::read_to_string("/proc/self/status")
+        .is_ok_and(|status| has_capability(&status, CAP_SYS_NICE));
+    let mut limit = libc::rlimit {
+        rlim_cur: 0,
+        rlim_max: 0,
+    };
+    // SAFETY: `limit` is a valid rlimit to fill.
+    let rtprio =
+        unsafe { libc::getrlimit(libc::RLIMIT_RTPRIO, &mut limit) } == 0 && limit.rlim_cur > 0;
+    Check {
+        item: Item::Realtime,
+        state: if capable || rtprio {
+            State::Ok
+        } else {
+            State::Optional
+        },
+        detail: if capable {
+            "CAP_SYS_NICE".to_owned()
+        } else {
+            format!("RLIMIT_RTPRIO {}", limit.rlim_cur)
+        },
+    }
+}
+
+/// Whether the effective set in a `/proc/<pid>/status` dump has `bit`.
+fn has_capability(status: &str, bit: u32) -> bool {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("CapEff:"))
+        .and_then(|mask| u64::from_str_radix(mask.trim(), 16).ok())
+        .is_some_and(|mask| mask & (1 << bit) != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn effective_capabilities_are_read_from_proc_status() {
+        let status = "Name:\tnocaps\nCapInh:\t0000000000000000\nCapPrm:\t0000000000800000\n\
+                      CapEff:\t0000000000800000\nCapBnd:\t000001ffffffffff\n";
+        assert!(has_capability(status, CAP_SYS_NICE));
+        assert!(!has_capability(status, 21));
+        assert!(!has_capability(
+            "CapPrm:\t0000000000800000\nCapEff:\t0000000000000000\n",
+            CAP_SYS_NICE
+        ));
+        assert!(!has_capability("Name:\tnocaps\n", CAP_SYS_NICE));
+    }
+}
@@ -0,0 +1,91 @@
+use anyhow::{Context, Result};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How long one batch of key events may take before its worker counts as stuck.
+/// Healthy batches take microseconds; this only trips on a real hang.
+const STALL_LIMIT: Duration = Duration::from_secs(3);
+const CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Marks when a worker started its current piece of work. Waiting for input is
+/// idle, so a keyboard nobody types on never looks stalled.
+pub struct Heartbeat {
+    name: String,
+    epoch: Instant,
+    // Milliseconds after `epoch`, plus one; zero while idle.
+    busy_since: AtomicU64,
+}
+
+impl Heartbeat {
+    pub fn new(name: impl Into<String>) -> Arc<Self> {
+        Arc::new(Self {
+            name: name.into(),
+            epoch: Instant::now(),
+            busy_since: AtomicU64::new(0),
+        })
+    }
+
+    pub fn busy(&self) {
+        self.busy_at(Instant::now());
+    }
+
+    pub fn idle(&self) {
+        self.busy_since.store(0, Ordering::Relaxed);
+    }
+
+    fn busy_at(&self, now: Instant) {
+        let since = now.duration_since(self.epoch).as_millis() as u64 + 1;
+        self.busy_since.store(since, Ordering::Relaxed);
+    }
+
+    fn stalled(&self, now: Instant) -> bool {
+        match self.busy_since.load(Ordering::Relaxed) {
+            0 => false,
+            since => {
+                let started = self.epoch + Duration::from_millis(since - 1);
+                now.saturating_duration_since(started) > STALL_LIMIT
+            }
+        }
+    }
+}
+
+/// Checks `heartbeats` on a thread of its own and calls `on_stall` once, with
+/// the name of the first one that has been busy for longer than `STALL_LIMIT`.
+pub fn supervise(
+    heartbeats: Vec<Arc<Heartbeat>>,
+    on_stall: impl FnOnce(&str) + Send + 'static,
+) -> Result<()> {
+    thread::Builder::new()
+        .name("nocaps-watchdog".to_owned())
+        .spawn(move || loop {
+            thread::sleep(CHECK_INTERVAL);
+            let now = Instant::now();
+            if let Some(stalled) = heartbeats.iter().find(|beat| beat.stalled(now)) {
+                on_stall(&stalled.name);
+                return;
+            }
+        })
+        .context("start keyboard watchdog")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_long_busy_stretches_count_as_stalls() {
+        let beat = Heartbeat::new("keyboard");
+        let start = Instant::now();
+        assert!(!beat.stalled(start + Duration::from_secs(60)));
+
+        beat.busy_at(start);
+        assert!(!beat.stalled(start + Duration::from_secs(1)));
+        assert!(beat.stalled(start + STALL_LIMIT + Duration::from_millis(1)));
+
+        beat.idle();
+        assert!(!beat.stalled(start + Duration::from_secs(60)));
+    }
+}
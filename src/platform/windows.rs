@@ -1,10 +1,13 @@
+use super::gesture::{Step, ToggleGesture};
 use crate::config::{Action, InputKey, RuntimeBindings};
 use anyhow::{anyhow, Result};
 use log::info;
-use std::sync::atomic::{AtomicBool, AtomicU16, Ordering};
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, AtomicU16, AtomicU64, Ordering};
 use std::sync::mpsc;
 use std::sync::{Arc, OnceLock};
 use std::thread::{self, JoinHandle};
+use std::time::Instant;
 use windows::Win32::Foundation::{LPARAM, LRESULT, WPARAM};
 use windows::Win32::System::Threading::*;
 use windows::Win32::UI::Input::KeyboardAndMouse::*;
@@ -13,8 +16,15 @@ use windows::Win32::UI::WindowsAndMessaging::*;
 static RUNTIME: OnceLock<Arc<RuntimeBindings>> = OnceLock::new();
 static HELD_TARGETS: [AtomicU16; InputKey::COUNT] = [const { AtomicU16::new(0) }; InputKey::COUNT];
 static CAPS_CAPTURED: AtomicBool = AtomicBool::new(false);
+// The `RuntimeBindings` generation that `HELD_TARGETS` were pressed under.
+static GENERATION: AtomicU64 = AtomicU64::new(0);
 const RELEASE_ALREADY_SENT: u16 = u16::MAX;
 
+thread_local! {
+    // The hook only ever runs on the input thread.
+    static GESTURE: RefCell<ToggleGesture> = RefCell::new(ToggleGesture::default());
+}
+
 pub struct KeyboardManager {
     thread_id: u32,
     thread: Option<JoinHandle<()>>,
@@ -86,6 +96,14 @@ unsafe extern "system" fn keyboard_hook(code: i32, wparam: WPARAM, lparam: LPARA
         return call_next_hook(code, wparam, lparam);
     }
 
+    // Bindings changed since the last key: let go of outputs held under the old
+    // ones, and swallow their physical releases later.
+    if let Some(generation) = RUNTIME.get().map(|runtime| runtime.generation()) {
+        if GENERATION.swap(generation, Ordering::Relaxed) != generation {
+            release_held_targets(true);
+        }
+    }
+
     let virtual_key = event.vkCode as i32;
     let source = key_from_windows_scan(event.scanCode, event.flags.0 & LLKHF_EXTENDED.0 != 0);
     let is_down = wparam.0 == WM_KEYDOWN as usize || wparam.0 == WM_SYSKEYDOWN as usize;
@@ -95,17 +113,34 @@ unsafe extern "system" fn keyboard_hook(code: i32, wparam: WPARAM, lparam: LPARA
     }
 
     if virtual_key == VK_CAPITAL.0 as i32 {
+        GESTURE.with_borrow_mut(|gesture| gesture.caps(is_down));
         if is_up && CAPS_CAPTURED.swap(false, Ordering::Relaxed) {
+            set_layer_active(false);
             release_held_targets(true);
             return LRESULT(1);
         }
         if is_down && runtime_enabled() {
             force_caps_lock_off();
             CAPS_CAPTURED.store(true, Ordering::Relaxed);
+            set_layer_active(true);
             return LRESULT(1);
         }
     }
 
+    let hotkey = RUNTIME.get().and_then(|runtime| runtime.toggle_hotkey());
+    let step =
+        GESTURE.with_borrow_mut(|gesture| gesture.key(source, is_down, hotkey, Instant::now()));
+    if step == Step::Complete {
+        if let Some(runtime) = RUNTIME.get() {
+            runtime.request_toggle();
+        }
+    }
+    // The toggle key is reserved while the layer is held; with remapping
+    // disabled it passes through untouched.
+    if step != Step::Unrelated && CAPS_CAPTURED.load(Ordering::Relaxed) {
+        return LRESULT(1);
+    }
+
     if is_up {
         if let Some(source) = source {
             let target = HELD_TARGETS[source.index()].swap(0, Ordering::Relaxed);
@@ -121,8 +156,12 @@ unsafe extern "system" fn keyboard_hook(code: i32, wparam: WPARAM, lparam: LPARA
 
     if is_down && CAPS_CAPTURED.load(Ordering::Relaxed) {
         if let Some(source) = source {
-            if let Some(target) = configured_target(source) {
-                HELD_TARGETS[source.index()].store(target, Ordering::Relaxed);
+            if let Some((runtime, action)) = configured_action(source) {
+                let target = windows_code(action);
+                // Auto-repeat finds the target already held and is not counted.
+                if HELD_TARGETS[source.index()].swap(target, Ordering::Relaxed) != target {
+                    runtime.record_use(source, action);
+                }
                 send_key(target, true);
                 return LRESULT(1);
             }
@@ -136,14 +175,21 @@ fn call_next_hook(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
     unsafe { CallNextHookEx(None, code, wparam, lparam) }
 }
 
-fn configured_target(source: InputKey) -> Option<u16> {
-    RUNTIME.get()?.action_for(source).map(windows_code)
+fn configured_action(source: InputKey) -> Option<(&'static Arc<RuntimeBindings>, Action)> {
+    let runtime = RUNTIME.get()?;
+    Some((runtime, runtime.action_for(source)?))
 }
 
 fn runtime_enabled() -> bool {
     RUNTIME.get().is_some_and(|runtime| runtime.is_enabled())
 }
 
+fn set_layer_active(active: bool) {
+    if let Some(runtime) = RUNTIME.get() {
+        runtime.set_layer_active(active);
+    }
+}
+
 fn send_key(code: u16, down: bool) {
     let mut flags = if down {
         KEYBD_EVENT_FLAGS(0)
@@ -0,0 +1,289 @@
+use anyhow::{anyhow, Context, Result};
+use directories::BaseDirs;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+const UNIT_NAME: &str = "nocaps.service";
+const DESKTOP_NAME: &str = "nocaps.desktop";
+
+/// How nocaps is started with the user's session.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Method {
+    /// A systemd user unit, used whenever a systemd user manager is running.
+    Systemd,
+    /// An XDG autostart entry, for desktops without a systemd user manager.
+    Autostart,
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str(match self {
+            Self::Systemd => "systemd user unit",
+            Self::Autostart => "XDG autostart entry",
+        })
+    }
+}
+
+/// An autostart entry found on disk.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Installation {
+    pub method: Method,
+    pub path: PathBuf,
+}
+
+impl fmt::Display for Installation {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{} {}", self.method, self.path.display())
+    }
+}
+
+impl Installation {
+    /// The command line the entry runs, as written in the file.
+    pub fn command(&self) -> Option<String> {
+        let key = match self.method {
+            Method::Systemd => "ExecStart=",
+            Method::Autostart => "Exec=",
+        };
+        fs::read_to_string(&self.path)
+            .ok()?
+            .lines()
+            .find_map(|line| line.strip_prefix(key).map(str::to_owned))
+    }
+
+    /// What systemd reports for the unit, e.g. "enabled, active". Autostart
+    /// entries have no state beyond being present.
+    pub fn state(&self) -> Option<String> {
+        if self.method != Method::Systemd {
+            return None;
+        }
+        let query = |verb: &str| {
+            Command::new("systemctl")
+                .args(["--user", verb, UNIT_NAME])
+                .output()
+                .ok()
+                .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_owned())
+        };
+        Some(format!("{}, {}", query("is-enabled")?, query("is-active")?))
+    }
+}
+
+/// Autostart files under the user configuration directory, usually `~/.config`.
+pub struct Service {
+    config_home: PathBuf,
+}
+
+impl Service {
+    pub fn discover() -> Result<Self> {
+        let base = BaseDirs::new()
+            .ok_or_else(|| anyhow!("could not determine the user configuration directory"))?;
+        Ok(Self {
+            config_home: base.config_dir().to_owned(),
+        })
+    }
+
+    fn unit_path(&self) -> PathBuf {
+        self.config_home
+            .join("systemd")
+            .join("user")
+            .join(UNIT_NAME)
+    }
+
+    fn desktop_path(&self) -> PathBuf {
+        self.config_home.join("autostart").join(DESKTOP_NAME)
+    }
+
+    /// The installed entry, if any. A systemd unit takes precedence.
+    pub fn installed(&self) -> Option<Installation> {
+        [
+            (Method::Systemd, self.unit_path()),
+            (Method::Autostart, self.desktop_path()),
+        ]
+        .into_iter()
+        .find(|(_, path)| path.exists())
+        .map(|(method, path)| Installation { method, path })
+    }
+
+    /// Starts the running executable with every later session, replacing any
+    /// previous entry. A systemd user unit is preferred when a user manager is
+    /// running; otherwise an XDG autostart entry is written.
+    pub fn install(&self, headless: bool) -> Result<Installation> {
+        let executable = std::env::current_exe().context("locate the nocaps executable")?;
+        self.uninstall()?;
+        let installation = if systemd_available() {
+            let path = self.unit_path();
+            write(&path, &unit_file(&executable, headless))?;
+            systemctl(&["daemon-reload"])?;
+            systemctl(&["enable", UNIT_NAME])?;
+            Installation {
+                method: Method::Systemd,
+                path,
+            }
+        } else {
+            let path = self.desktop_path();
+            write(&path, &desktop_entry(&executable, headless))?;
+            Installation {
+                method: Method::Autostart,
+                path,
+            }
+        };
+        Ok(installation)
+    }
+
+    /// Removes every entry and returns the files that were deleted.
+    pub fn uninstall(&self) -> Result<Vec<PathBuf>> {
+        let mut removed = Vec::new();
+        let unit = self.unit_path();
+        if unit.exists() {
+            // Disabling first removes the `.wants` link that would otherwise dangle.
+            if let Err(error) = systemctl(&["disable", UNIT_NAME]) {
+                log::warn!("{error:#}");
+            }
+            fs::remove_file(&unit).with_context(|| format!("remove {}", unit.display()))?;
+            let _ = systemctl(&["daemon-reload"]);
+            removed.push(unit);
+        }
+        let desktop = self.desktop_path();
+        if desktop.exists() {
+            fs::remove_file(&desktop).with_context(|| format!("remove {}", desktop.display()))?;
+            removed.push(desktop);
+        }
+        Ok(removed)
+    }
+}
+
+fn write(path: &Path, contents: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).with_context(|| format!("create {}", parent.display()))?;
+    }
+    fs::write(path, contents).with_context(|| format!("write {}", path.display()))
+}
+
+/// True when this session has a systemd user manager to hand the unit to.
+fn systemd_available() -> bool {
+    Command::new("systemctl")
+        .args(["--user", "show-environment"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+fn systemctl(args: &[&str]) -> Result<()> {
+    let command = format!("systemctl --user {}", args.join(" "));
+    let output = Command::new("systemctl")
+        .arg("--user")
+        .args(args)
+        .output()
+        .with_context(|| format!("run {command}"))?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{command} failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(())
+}
+
+/// The window needs the graphical session's display, so the unit follows that
+/// session; a headless remapper only needs the user manager.
+fn unit_file(executable: &Path, headless: bool) -> String {
+    let (session, target) = if headless {
+        ("", "default.target")
+    } else {
+        (
+            "PartOf=graphical-session.target\nAfter=graphical-session.target\n",
+            "graphical-session.target",
+        )
+    };
+    format!(
+        "[Unit]\n\
+         Description=nocaps keyboard remapper\n\
+         {session}\n\
+         [Service]\n\
+         ExecStart={}\n\
+         Restart=on-failure\n\
+         RestartSec=2\n\
+         \n\
+         [Install]\n\
+         WantedBy={target}\n",
+        command_line(executable, headless, systemd_quote)
+    )
+}
+
+fn desktop_entry(executable: &Path, headless: bool) -> String {
+    format!(
+        "[Desktop Entry]\n\
+         Type=Application\n\
+         Name=nocaps\n\
+         Comment=Caps Lock layer keyboard remapper\n\
+         Exec={}\n\
+         Terminal=false\n\
+         X-GNOME-Autostart-enabled=true\n",
+        command_line(executable, headless, desktop_quote)
+    )
+}
+
+fn command_line(executable: &Path, headless: bool, quote: fn(&str) -> String) -> String {
+    let mut command = quote(&executable.to_string_lossy());
+    if headless {
+        command.push_str(" --headless");
+    }
+    command
+}
+
+/// Quotes a word for `ExecStart=`, where `%` starts a specifier and `$` a variable.
+fn systemd_quote(word: &str) -> String {
+    let escaped = word
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('%', "%%")
+        .replace('$', "$$");
+    format!("\"{escaped}\"")
+}
+
+/// Quotes a word for `Exec=`. The desktop entry spec escapes `"`, `` ` ``, `$` and
+/// `\` inside quotes, then escapes backslashes again for the string value, and
+/// reserves `%` for field codes.
+fn desktop_quote(word: &str) -> String {
+    let mut quoted = String::from("\"");
+    for character in word.chars() {
+        match character {
+            '"' | '`' | '$' => {
+                quoted.push_str("\\\\");
+                quoted.push(character);
+            }
+            '\\' => quoted.push_str("\\\\\\\\"),
+            '%' => quoted.push_str("%%"),
+            _ => quoted.push(character),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn units_follow_the_graphical_session_unless_headless() {
+        let unit = unit_file(Path::new("/usr/bin/nocaps"), false);
+        assert!(unit.contains("ExecStart=\"/usr/bin/nocaps\"\n"));
+        assert!(unit.contains("WantedBy=graphical-session.target"));
+
+        let unit = unit_file(Path::new("/usr/bin/nocaps"), true);
+        assert!(unit.contains("ExecStart=\"/usr/bin/nocaps\" --headless\n"));
+        assert!(unit.contains("WantedBy=default.target"));
+        assert!(!unit.contains("graphical-session"));
+    }
+
+    #[test]
+    fn executable_paths_are_quoted_for_each_format() {
+        assert_eq!(systemd_quote("/opt/no caps/100%"), "\"/opt/no caps/100%%\"");
+        assert_eq!(systemd_quote("/home/$USER"), "\"/home/$$USER\"");
+        assert_eq!(desktop_quote("/opt/no caps/100%"), "\"/opt/no caps/100%%\"");
+        assert_eq!(desktop_quote("/a\\b$c"), "\"/a\\\\\\\\b\\\\$c\"");
+    }
+}
@@ -0,0 +1,191 @@
+use crate::config::{Action, ConfigStore, InputKey, RuntimeBindings};
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Counts are written at most this often while they change, and once at exit.
+const SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How often each action was invoked from the layer, and with which key. Only
+/// totals are kept, never order or timing, and keys typed outside the layer are
+/// not counted, so no text can be recovered from them.
+pub struct Usage {
+    actions: [AtomicU64; Action::COUNT],
+    keys: [AtomicU64; InputKey::COUNT],
+    changed: AtomicBool,
+}
+
+impl Default for Usage {
+    fn default() -> Self {
+        Self {
+            actions: [const { AtomicU64::new(0) }; Action::COUNT],
+            keys: [const { AtomicU64::new(0) }; InputKey::COUNT],
+            changed: AtomicBool::new(false),
+        }
+    }
+}
+
+impl Usage {
+    pub fn record(&self, key: InputKey, action: Action) {
+        self.actions[action.index()].fetch_add(1, Ordering::Relaxed);
+        self.keys[key.index()].fetch_add(1, Ordering::Relaxed);
+        self.changed.store(true, Ordering::Relaxed);
+    }
+
+    pub fn counts(&self) -> UsageCounts {
+        let nonzero =
+            |counter: &AtomicU64| Some(counter.load(Ordering::Relaxed)).filter(|n| *n > 0);
+        UsageCounts {
+            actions: Action::ALL
+                .iter()
+                .filter_map(|action| Some((*action, nonzero(&self.actions[action.index()])?)))
+                .collect(),
+            keys: self
+                .keys
+                .iter()
+                .zip(InputKey::ALL)
+                .filter_map(|(counter, key)| Some((key, nonzero(counter)?)))
+                .collect(),
+        }
+    }
+
+    /// Adds previously saved totals, at startup.
+    pub fn restore(&self, counts: &UsageCounts) {
+        for (action, count) in &counts.actions {
+            self.actions[action.index()].fetch_add(*count, Ordering::Relaxed);
+        }
+        for (key, count) in &counts.keys {
+            self.keys[key.index()].fetch_add(*count, Ordering::Relaxed);
+        }
+    }
+
+    #[cfg(any(feature = "gui", test))]
+    pub fn reset(&self) {
+        for counter in self.actions.iter().chain(&self.keys) {
+            counter.store(0, Ordering::Relaxed);
+        }
+        self.changed.store(true, Ordering::Relaxed);
+    }
+
+    fn take_changed(&self) -> bool {
+        self.changed.swap(false, Ordering::Relaxed)
+    }
+}
+
+/// The saved form of `Usage`: totals by name, leaving out anything never used.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct UsageCounts {
+    pub actions: BTreeMap<Action, u64>,
+    pub keys: BTreeMap<InputKey, u64>,
+}
+
+/// `usage.json`, next to `config.json`.
+#[derive(Clone, Debug)]
+pub struct UsageStore {
+    path: PathBuf,
+}
+
+impl UsageStore {
+    pub fn beside(config: &ConfigStore) -> Result<Self> {
+        let directory = config
+            .path()
+            .parent()
+            .ok_or_else(|| anyhow!("configuration path has no parent"))?;
+        Ok(Self {
+            path: directory.join("usage.json"),
+        })
+    }
+
+    /// The saved totals, or none when nothing has been saved yet.
+    pub fn load(&self) -> Result<UsageCounts> {
+        if !self.path.exists() {
+            return Ok(UsageCounts::default());
+        }
+        let bytes =
+            fs::read(&self.path).with_context(|| format!("read {}", self.path.display()))?;
+        serde_json::from_slice(&bytes).with_context(|| format!("parse {}", self.path.display()))
+    }
+
+    pub fn save(&self, counts: &UsageCounts) -> Result<()> {
+        fs::write(&self.path, serde_json::to_vec_pretty(counts)?)
+            .with_context(|| format!("write {}", self.path.display()))
+    }
+}
+
+/// Saves the runtime's usage totals in the background while they change. The
+/// last totals are saved when this value is dropped.
+pub struct UsageRecorder {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl UsageRecorder {
+    pub fn start(runtime: Arc<RuntimeBindings>, store: UsageStore) -> Result<Self> {
+        // Fails rather than start from zero and overwrite a file it could not read.
+        runtime.usage().restore(&store.load()?);
+        let (stop, stopped) = mpsc::channel();
+        let thread = thread::Builder::new()
+            .name("nocaps-usage".to_owned())
+            .spawn(move || loop {
+                let stopping = stopped.recv_timeout(SAVE_INTERVAL).is_ok();
+                if runtime.usage().take_changed() {
+                    if let Err(error) = store.save(&runtime.usage().counts()) {
+                        log::warn!("could not save usage statistics: {error:#}");
+                    }
+                }
+                if stopping {
+                    return;
+                }
+            })
+            .context("start usage statistics thread")?;
+        Ok(Self {
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for UsageRecorder {
+    fn drop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_round_trip_through_usage_json() {
+        let usage = Usage::default();
+        usage.record(InputKey::I, Action::ArrowUp);
+        usage.record(InputKey::I, Action::ArrowUp);
+        usage.record(InputKey::A, Action::LeftControl);
+        let counts = usage.counts();
+        assert_eq!(counts.actions[&Action::ArrowUp], 2);
+        assert_eq!(counts.keys.len(), 2);
+
+        let directory = std::env::temp_dir().join(format!("nocaps-usage-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let store = UsageStore::beside(&ConfigStore::at(directory.join("config.json"))).unwrap();
+        store.save(&counts).unwrap();
+        let restored = Usage::default();
+        restored.restore(&store.load().unwrap());
+        assert_eq!(restored.counts(), counts);
+
+        restored.reset();
+        assert_eq!(restored.counts(), UsageCounts::default());
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
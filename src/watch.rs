@@ -0,0 +1,79 @@
+use crate::config::{Config, ConfigStore};
+use anyhow::{anyhow, Context, Result};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+// Editors save in several steps (truncate and write, or write a temporary file
+// and rename it over the original). Wait for the burst to go quiet before reading.
+const SETTLE: Duration = Duration::from_millis(150);
+
+/// Reloads the configuration file whenever it changes on disk. The watcher stops
+/// when this value is dropped.
+pub struct ConfigWatcher {
+    _watcher: RecommendedWatcher,
+}
+
+impl ConfigWatcher {
+    /// Calls `on_change` with the freshly validated file contents, or with the
+    /// error that prevented loading them, after every settled burst of edits.
+    /// Writes made by `ConfigStore::save` are reported too; callers compare the
+    /// result with the configuration they already apply.
+    pub fn new<F>(store: ConfigStore, on_change: F) -> Result<Self>
+    where
+        F: Fn(Result<Config>) + Send + 'static,
+    {
+        // Watch the directory rather than the file: a rename-over save replaces
+        // the inode, which would silently end a watch placed on the file itself.
+        let directory = store
+            .path()
+            .parent()
+            .ok_or_else(|| anyhow!("configuration path has no parent"))?
+            .to_owned();
+        let (events_tx, events) = mpsc::channel();
+        let mut watcher =
+            notify::recommended_watcher(events_tx).context("create configuration watcher")?;
+        watcher
+            .watch(&directory, RecursiveMode::NonRecursive)
+            .with_context(|| format!("watch {}", directory.display()))?;
+
+        thread::Builder::new()
+            .name("nocaps-config-watch".to_owned())
+            .spawn(move || run(store, events, on_change))
+            .context("start configuration watcher")?;
+        Ok(Self { _watcher: watcher })
+    }
+}
+
+fn run<F>(store: ConfigStore, events: mpsc::Receiver<notify::Result<notify::Event>>, on_change: F)
+where
+    F: Fn(Result<Config>),
+{
+    let file_name = store.path().file_name().map(ToOwned::to_owned);
+    while let Ok(event) = events.recv() {
+        let touches_config = match event {
+            // Only content changes matter; reading the file ourselves raises
+            // access events that must not feed back into another reload.
+            Ok(event) => {
+                matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_))
+                    && event
+                        .paths
+                        .iter()
+                        .any(|path| path.file_name() == file_name.as_deref())
+            }
+            Err(error) => {
+                log::warn!("configuration watcher error: {error}");
+                false
+            }
+        };
+        if !touches_config {
+            continue;
+        }
+
+        while events.recv_timeout(SETTLE).is_ok() {}
+        if store.path().exists() {
+            on_change(store.load());
+        }
+    }
+}